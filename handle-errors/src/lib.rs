@@ -1,5 +1,7 @@
+use serde::Serialize;
 use warp::{
-    reject::Reject, http::StatusCode, Rejection, Reply,
+    reject::{Reject, InvalidQuery, MethodNotAllowed, MissingHeader, PayloadTooLarge, UnsupportedMediaType},
+    http::{header::{CONTENT_TYPE, RETRY_AFTER}, HeaderValue, StatusCode}, Rejection, Reply,
     filters::{
         body::BodyDeserializeError, cors::CorsForbidden
     }
@@ -11,13 +13,18 @@ use reqwest_middleware::Error as MiddlewareReqwestError;
 
 
 const DUPLICATE_KEY: u32 = 23505;
+const FOREIGN_KEY_VIOLATION: u32 = 23503;
+const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
 
 // catch all the possible error types here
 #[derive(Debug)]
 pub enum WarpError {
     ParseError(std::num::ParseIntError),
     MissingParameters,
+    ValidationError(Vec<FieldError>),
+    NotFound,
     DatabaseQueryError(sqlx::Error),
+    AccountAlreadyExists,
     ClientError(APILayerError),
     ServerError(APILayerError),
     ReqwestAPIError(ReqwestError),
@@ -25,7 +32,16 @@ pub enum WarpError {
     WrongPassword,
     Unauthorized,
     CannotDecryptToken,
+    RetiredSigningKey,
+    SessionRevoked,
+    InvalidRefreshToken,
+    InvalidMetricsToken,
+    EmailNotVerified,
+    InvalidEmailToken,
     ArgonLibraryError(ArgonError),
+    // seconds until the client may try again
+    TooManyRequests(u64),
+    AccountLocked(u64),
 }
 
 // implement display for the WarpErrors
@@ -34,15 +50,26 @@ impl std::fmt::Display for WarpError {
         match &*self { 
             Self::ParseError(ref err) => write!(f, "WarpError parsing parameter: {}", err),
             Self::MissingParameters => write!(f, "Some parameters are missing"),
+            Self::ValidationError(errors) => write!(f, "{} invalid field(s)", errors.len()),
+            Self::NotFound => write!(f, "Requested resource does not exist"),
             Self::ReqwestAPIError(err) => write!(f, "External API Error: {}", err),
             Self::MiddlewareReqwestAPIError(err) => write!(f, "External API error: {}", err),
             Self::DatabaseQueryError(_) => write!(f, "Cannot update, invalid Data") ,
+            Self::AccountAlreadyExists => write!(f, "Account already exist"),
             Self::ClientError(err) => write!(f, "External Client error: {}", err),
             Self::WrongPassword => write!(f, "Wrong password"),
             Self::CannotDecryptToken => write!(f, "Unable to parse login authorization token"),
+            Self::RetiredSigningKey => write!(f, "Authorization token was signed with a retired key"),
+            Self::SessionRevoked => write!(f, "Login session was revoked or has expired"),
+            Self::InvalidRefreshToken => write!(f, "Refresh token is invalid, expired or already used"),
+            Self::InvalidMetricsToken => write!(f, "Metrics require a valid bearer token"),
+            Self::EmailNotVerified => write!(f, "Email address is not verified yet"),
+            Self::InvalidEmailToken => write!(f, "Token is invalid, expired or already used"),
             Self::Unauthorized => write!(f, "No permission to change the underlying resource"),
             Self::ArgonLibraryError(_) => write!(f, "Cannot verify password"),
             Self::ServerError(err) => write!(f, "External Server error: {}", err),
+            Self::TooManyRequests(secs) => write!(f, "Too many requests, try again in {} seconds", secs),
+            Self::AccountLocked(secs) => write!(f, "Too many failed logins, try again in {} seconds", secs),
         }
     }
 }
@@ -53,56 +80,213 @@ impl Reject for WarpError {}
 impl Reject for APILayerError {}
 
 // map the different possible error types, and handle them here
-#[instrument]
-pub async fn return_error(r: Rejection) -> Result<impl Reply, Rejection> {
-    if let Some(crate::WarpError::DatabaseQueryError(e)) = r.find() {
-        event!(Level::ERROR, "Database query error");
-        match e {
-            sqlx::Error::Database(err) => {
-                if err.code().unwrap().parse::<u32>().unwrap() == DUPLICATE_KEY {
-                    Ok(warp::reply::with_status("Account already exist".to_string(), StatusCode::UNPROCESSABLE_ENTITY))
-                } else {
-                    Ok(warp::reply::with_status("Cannot update data".to_string(), StatusCode::UNPROCESSABLE_ENTITY))
-                }
-            },
-            _ => {
-                Ok(warp::reply::with_status("Cannot update data".to_string(), StatusCode::UNPROCESSABLE_ENTITY))
-            }
+// every error leaves as an RFC 7807 `application/problem+json` body with a stable `code`;
+// `request_id` is the ID the server assigned to the failed request
+#[instrument(skip(request_id))]
+pub fn return_error(r: &Rejection, request_id: &str) -> warp::reply::Response {
+    let problem = if let Some(error) = r.find::<WarpError>() {
+        if let WarpError::DatabaseQueryError(e) = error {
+            event!(Level::ERROR, request_id = %request_id, "Database query error: {:?}", e);
         }
-        // Ok(warp::reply::with_status(crate::WarpError::DatabaseQueryError.to_string(),StatusCode::UNPROCESSABLE_ENTITY))
-    } else if let Some(crate::WarpError::ReqwestAPIError(e)) = r.find() {
-        event!(Level::ERROR, "{}", e);
-        Ok(warp::reply::with_status("Internal Server Error".to_string(), StatusCode::INTERNAL_SERVER_ERROR))   
-    } else if let Some(crate::WarpError::ClientError(e)) = r.find() {
-        event!(Level::ERROR, "{}", e);
-        Ok(warp::reply::with_status("Internal Server error".to_string(), StatusCode::INTERNAL_SERVER_ERROR))
-    } else if let Some(crate::WarpError::ServerError(e)) = r.find() {
-        event!(Level::ERROR, "{}", e);
-        Ok(warp::reply::with_status("Internal Server Error".to_string(), StatusCode::INTERNAL_SERVER_ERROR))
-    } else if let Some(crate::WarpError::MiddlewareReqwestAPIError(e)) = r.find() {
-        event!(Level::ERROR, "{}", e);
-        Ok(warp::reply::with_status("Internal Server error".to_string(), StatusCode::INTERNAL_SERVER_ERROR))
-    } else if let Some(crate::WarpError::Unauthorized) = r.find() {
-        event!(Level::ERROR, "Not matching account id");
-        Ok(warp::reply::with_status("No permission to change underlying resource".to_string(), StatusCode::UNAUTHORIZED,))
+        Problem::new(error.status(), error.code(), error.detail())
+            .with_errors(error.field_errors())
+            .with_retry_after(error.retry_after())
     } else if let Some(error) = r.find::<CorsForbidden>() {
-        event!(Level::ERROR, "CORS forbidden error: {}", error);
-        Ok(warp::reply::with_status(error.to_string(),StatusCode::FORBIDDEN))
+        Problem::new(StatusCode::FORBIDDEN, "cors_forbidden", error.to_string())
     } else if let Some(error) = r.find::<BodyDeserializeError>() {
-        event!(Level::ERROR, "Cannot deserizalize request body: {}", error);
-        Ok(warp::reply::with_status(error.to_string(),StatusCode::UNPROCESSABLE_ENTITY))
-    } else if let Some(crate::WarpError::WrongPassword) = r.find() {
-        event!(Level::ERROR, "Entered wrong password");
-        Ok(warp::reply::with_status("Wrong E-Mail/Password combination".to_string(), StatusCode::UNAUTHORIZED))
-    } else if let Some(error) = r.find::<WarpError>() {
-        event!(Level::ERROR, "{}", error);
-        Ok(warp::reply::with_status(error.to_string(),StatusCode::UNPROCESSABLE_ENTITY,)) 
+        Problem::new(StatusCode::BAD_REQUEST, "invalid_body", format!("Cannot deserialize request body: {}", error))
+    } else if let Some(error) = r.find::<InvalidQuery>() {
+        Problem::new(StatusCode::BAD_REQUEST, "invalid_query", error.to_string())
+    } else if let Some(error) = r.find::<MissingHeader>() {
+        if error.name().eq_ignore_ascii_case("authorization") {
+            Problem::new(StatusCode::UNAUTHORIZED, "missing_token", "Authorization header is missing")
+        } else {
+            Problem::new(StatusCode::BAD_REQUEST, "missing_header", error.to_string())
+        }
+    } else if let Some(error) = r.find::<UnsupportedMediaType>() {
+        Problem::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type", error.to_string())
+    } else if let Some(error) = r.find::<PayloadTooLarge>() {
+        Problem::new(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", error.to_string())
+    } else if let Some(error) = r.find::<MethodNotAllowed>() {
+        Problem::new(StatusCode::METHOD_NOT_ALLOWED, "method_not_allowed", error.to_string())
+    } else {
+        Problem::new(StatusCode::NOT_FOUND, "route_not_found", "Route not found")
+    };
+
+    if problem.status.is_server_error() {
+        event!(Level::ERROR, request_id = %request_id, code = problem.code, "{}", problem.detail);
     } else {
-        event!(Level::WARN, "Requested route was not found");
-        Ok(warp::reply::with_status("Route not found".to_string(),StatusCode::NOT_FOUND,))
+        event!(Level::WARN, request_id = %request_id, code = problem.code, "{}", problem.detail);
+    }
+    problem.into_reply(request_id)
+}
+
+/// A single invalid input field, reported in the `errors` list of a problem
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { field: field.into(), message: message.into() }
     }
 }
 
+// RFC 7807 problem details; `code` is what clients should match on, `detail` may change
+#[derive(Debug, Serialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    type_uri: String,
+    title: String,
+    #[serde(serialize_with = "serialize_status")]
+    status: StatusCode,
+    detail: String,
+    code: &'static str,
+    request_id: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+    // sent as the `Retry-After` header
+    #[serde(skip)]
+    retry_after: Option<u64>,
+}
+
+impl Problem {
+    fn new(status: StatusCode, code: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            type_uri: format!("/problems/{}", code.replace('_', "-")),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status,
+            detail: detail.into(),
+            code,
+            request_id: String::new(),
+            errors: Vec::new(),
+            retry_after: None,
+        }
+    }
+
+    fn with_errors(mut self, errors: &[FieldError]) -> Self {
+        self.errors = errors.to_vec();
+        self
+    }
+
+    fn with_retry_after(mut self, retry_after: Option<u64>) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    fn into_reply(mut self, request_id: &str) -> warp::reply::Response {
+        self.request_id = request_id.to_owned();
+        let status = self.status;
+        let mut response = warp::reply::with_status(
+            warp::reply::with_header(warp::reply::json(&self), CONTENT_TYPE, PROBLEM_CONTENT_TYPE),
+            status,
+        ).into_response();
+        if let Some(secs) = self.retry_after {
+            response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
+    }
+}
+
+fn serialize_status<S: serde::Serializer>(status: &StatusCode, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u16(status.as_u16())
+}
+
+impl WarpError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::ParseError(_) | Self::MissingParameters => StatusCode::BAD_REQUEST,
+            Self::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::DatabaseQueryError(e) => match database_error_code(e) {
+                Some(DUPLICATE_KEY) => StatusCode::CONFLICT,
+                Some(FOREIGN_KEY_VIOLATION) => StatusCode::UNPROCESSABLE_ENTITY,
+                _ if matches!(e, sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::AccountAlreadyExists => StatusCode::CONFLICT,
+            Self::ClientError(_) | Self::ServerError(_)
+                | Self::ReqwestAPIError(_) | Self::MiddlewareReqwestAPIError(_) => StatusCode::BAD_GATEWAY,
+            Self::WrongPassword | Self::CannotDecryptToken | Self::RetiredSigningKey
+                | Self::SessionRevoked | Self::InvalidRefreshToken | Self::InvalidMetricsToken => StatusCode::UNAUTHORIZED,
+            Self::Unauthorized | Self::EmailNotVerified => StatusCode::FORBIDDEN,
+            Self::InvalidEmailToken => StatusCode::BAD_REQUEST,
+            Self::ArgonLibraryError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::TooManyRequests(_) | Self::AccountLocked(_) => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    /// Stable, machine-readable identifier of the error; never change an existing one
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ParseError(_) => "invalid_parameter",
+            Self::MissingParameters => "missing_parameters",
+            Self::ValidationError(_) => "validation_failed",
+            Self::NotFound => "not_found",
+            Self::DatabaseQueryError(e) => match database_error_code(e) {
+                Some(DUPLICATE_KEY) => "conflict",
+                Some(FOREIGN_KEY_VIOLATION) => "invalid_reference",
+                _ if matches!(e, sqlx::Error::RowNotFound) => "not_found",
+                _ => "database_error",
+            },
+            Self::AccountAlreadyExists => "account_exists",
+            Self::ClientError(_) | Self::ServerError(_)
+                | Self::ReqwestAPIError(_) | Self::MiddlewareReqwestAPIError(_) => "profanity_check_failed",
+            Self::WrongPassword => "invalid_credentials",
+            Self::Unauthorized => "forbidden",
+            Self::CannotDecryptToken => "invalid_token",
+            Self::RetiredSigningKey => "token_key_retired",
+            Self::SessionRevoked => "session_revoked",
+            Self::InvalidRefreshToken => "invalid_refresh_token",
+            Self::InvalidMetricsToken => "invalid_metrics_token",
+            Self::EmailNotVerified => "email_not_verified",
+            Self::InvalidEmailToken => "invalid_email_token",
+            Self::ArgonLibraryError(_) => "password_verification_failed",
+            Self::TooManyRequests(_) => "rate_limited",
+            Self::AccountLocked(_) => "account_locked",
+        }
+    }
+
+    /// Human-readable explanation; internals like SQL errors stay in the logs
+    pub fn detail(&self) -> String {
+        match self {
+            Self::DatabaseQueryError(e) => match database_error_code(e) {
+                Some(DUPLICATE_KEY) => "Resource already exists".to_string(),
+                Some(FOREIGN_KEY_VIOLATION) => "Referenced resource does not exist".to_string(),
+                _ if matches!(e, sqlx::Error::RowNotFound) => "Requested resource does not exist".to_string(),
+                _ => "Cannot update data".to_string(),
+            },
+            Self::WrongPassword => "Wrong E-Mail/Password combination".to_string(),
+            Self::ClientError(_) | Self::ServerError(_)
+                | Self::ReqwestAPIError(_) | Self::MiddlewareReqwestAPIError(_) => "Content could not be checked for profanity, please try again later".to_string(),
+            error => error.to_string(),
+        }
+    }
+
+    pub fn field_errors(&self) -> &[FieldError] {
+        match self {
+            Self::ValidationError(errors) => errors,
+            _ => &[],
+        }
+    }
+
+    /// Seconds the client should wait before retrying, for `429` responses
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            Self::TooManyRequests(secs) | Self::AccountLocked(secs) => Some(*secs),
+            _ => None,
+        }
+    }
+}
+
+fn database_error_code(error: &sqlx::Error) -> Option<u32> {
+    error.as_database_error()
+        .and_then(|e| e.code())
+        .and_then(|code| code.parse::<u32>().ok())
+}
+
 
 // API struct used for `profanity.rs`
 #[derive(Debug, Clone)]
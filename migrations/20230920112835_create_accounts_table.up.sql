@@ -1,6 +1,6 @@
 -- Add up migration script here
-CREATE TABLE IF NOT EXISTS accounts {
+CREATE TABLE IF NOT EXISTS accounts (
     id serial NOT NULL,
     email VARCHAR(255) NOT NULL PRIMARY KEY,
     password VARCHAR(255) NOT NULL
-};
\ No newline at end of file
+);
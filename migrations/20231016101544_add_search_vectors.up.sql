@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- kept up to date by Postgres itself; titles weigh more than the body text
+ALTER TABLE questions
+ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', content), 'B')
+) STORED;
+
+ALTER TABLE answers
+ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+    to_tsvector('english', content)
+) STORED;
+
+CREATE INDEX IF NOT EXISTS questions_search_vector_idx ON questions USING GIN (search_vector);
+CREATE INDEX IF NOT EXISTS answers_search_vector_idx ON answers USING GIN (search_vector);
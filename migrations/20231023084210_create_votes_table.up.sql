@@ -0,0 +1,15 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS votes (
+    id serial PRIMARY KEY,
+    account_id integer NOT NULL,
+    question_id integer REFERENCES questions ON DELETE CASCADE,
+    answer_id integer REFERENCES answers ON DELETE CASCADE,
+    value smallint NOT NULL CHECK (value IN (-1, 1)),
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    -- a vote is either on a question or on an answer
+    CHECK ((question_id IS NULL) <> (answer_id IS NULL))
+);
+
+-- one vote per account and target; also serve the score lookups by target
+CREATE UNIQUE INDEX IF NOT EXISTS votes_question_account_idx ON votes (question_id, account_id) WHERE question_id IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS votes_answer_account_idx ON votes (answer_id, account_id) WHERE answer_id IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE questions
+ADD COLUMN accepted_answer_id integer REFERENCES answers ON DELETE SET NULL;
@@ -0,0 +1,18 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS question_revisions (
+    id serial PRIMARY KEY,
+    question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+    -- counts up from 1 (the question as first posted) per question
+    revision integer NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    content TEXT NOT NULL,
+    tags TEXT [],
+    editor_id integer,
+    reason TEXT,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (question_id, revision)
+);
+
+-- existing questions start their history with their current state
+INSERT INTO question_revisions (question_id, revision, title, content, tags, editor_id, created_on)
+SELECT id, 1, title, content, tags, account_id, created_on FROM questions;
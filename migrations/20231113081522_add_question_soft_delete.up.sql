@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- deleted questions stay in the table until the purge job removes them after the retention period
+ALTER TABLE questions
+ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS questions_deleted_at_idx ON questions (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS account_tokens;
+
+ALTER TABLE accounts
+DROP COLUMN IF EXISTS email_verified_on;
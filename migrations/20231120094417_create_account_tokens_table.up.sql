@@ -0,0 +1,19 @@
+-- Add up migration script here
+ALTER TABLE accounts
+ADD COLUMN IF NOT EXISTS email_verified_on TIMESTAMP;
+
+-- accounts from before email verification existed keep working
+UPDATE accounts SET email_verified_on = NOW() WHERE email_verified_on IS NULL;
+
+-- tokens sent by email; only their SHA-256 hash is stored
+CREATE TABLE IF NOT EXISTS account_tokens (
+    id serial PRIMARY KEY,
+    account_id integer NOT NULL,
+    purpose VARCHAR(32) NOT NULL CHECK (purpose IN ('verify_email', 'reset_password')),
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_on TIMESTAMP NOT NULL,
+    used_on TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS account_tokens_account_id_idx ON account_tokens (account_id);
@@ -0,0 +1,10 @@
+-- Add down migration script here
+-- anonymized content ends up with the account ID 0, which no account ever gets
+UPDATE questions SET account_id = 0 WHERE account_id IS NULL;
+UPDATE answers SET account_id = 0 WHERE account_id IS NULL;
+
+ALTER TABLE questions
+ALTER COLUMN account_id SET NOT NULL;
+
+ALTER TABLE answers
+ALTER COLUMN account_id SET NOT NULL;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- content of deleted accounts can be kept without an owner
+ALTER TABLE questions
+ALTER COLUMN account_id DROP NOT NULL;
+
+ALTER TABLE answers
+ALTER COLUMN account_id DROP NOT NULL;
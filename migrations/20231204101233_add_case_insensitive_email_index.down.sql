@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS accounts_email_lower_idx;
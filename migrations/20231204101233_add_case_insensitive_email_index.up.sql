@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- emails are unique regardless of case; fails if two existing accounts only differ in case,
+-- which have to be merged or renamed by hand first
+CREATE UNIQUE INDEX IF NOT EXISTS accounts_email_lower_idx ON accounts (LOWER(email));
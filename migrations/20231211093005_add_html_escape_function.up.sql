@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- search snippets are HTML with `<mark>` around the matches, so the text has to be escaped
+-- before `ts_headline` adds the markup
+CREATE OR REPLACE FUNCTION html_escape(text) RETURNS text AS $$
+    SELECT replace(replace(replace(replace($1, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '"', '&quot;')
+$$ LANGUAGE SQL IMMUTABLE STRICT;
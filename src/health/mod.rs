@@ -0,0 +1,259 @@
+// what an orchestrator asks before sending traffic: `GET /health/live` only tells that the process
+// answers, `GET /health/ready` checks every dependency a request may need
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use warp::http::StatusCode;
+use warp::Reply;
+use handle_errors::WarpError; // internal library
+
+use crate::config::HealthConfig;
+use crate::profanity::DynProfanityChecker;
+use crate::store::DynHealthStore;
+
+// what the profanity backend gets to check; clean, so a censoring bug can't break the probe
+const PROBE_TEXT: &str = "health check";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Up,
+    Down,
+}
+
+// why a check failed only goes to the logs; the endpoint is public and the errors name hosts,
+// users and versions
+#[derive(Debug, Serialize)]
+pub struct Check {
+    status: Status,
+    duration_ms: u64,
+}
+
+/// The answer of `GET /health/ready`; up only if every check is and the server is not shutting down
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    status: Status,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    shutting_down: bool,
+    checks: BTreeMap<&'static str, Check>,
+}
+
+/// Runs the readiness checks; cheap to clone into the route
+#[derive(Clone, Debug)]
+pub struct HealthChecker {
+    store: DynHealthStore,
+    profanity: DynProfanityChecker,
+    probe_profanity: bool,
+    timeout: Duration,
+    // shared by every clone, so the route sees the flag `main` sets
+    shutting_down: Arc<AtomicBool>,
+}
+
+impl HealthChecker {
+    pub fn new(store: DynHealthStore, profanity: DynProfanityChecker, config: &HealthConfig) -> Self {
+        Self {
+            store,
+            profanity,
+            probe_profanity: config.probe_profanity,
+            timeout: Duration::from_secs(config.timeout),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // from now on the instance reports not ready, whatever its dependencies say
+    pub fn start_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::Relaxed);
+    }
+
+    // the checks run side by side, so the slowest one decides how long this takes
+    pub async fn readiness(&self) -> Readiness {
+        // the checks don't matter anymore, the instance is about to go away
+        if self.shutting_down.load(Ordering::Relaxed) {
+            return Readiness { status: Status::Down, shutting_down: true, checks: BTreeMap::new() };
+        }
+        let (database, migrations, profanity) = tokio::join!(
+            self.check("database", self.ping_database()),
+            self.check("migrations", self.check_migrations()),
+            async {
+                match self.probe_profanity {
+                    true => Some(self.check("profanity", self.probe_profanity()).await),
+                    false => None,
+                }
+            },
+        );
+        let mut checks = BTreeMap::from([("database", database), ("migrations", migrations)]);
+        if let Some(profanity) = profanity {
+            checks.insert("profanity", profanity);
+        }
+        let status = match checks.values().all(|check| check.status == Status::Up) {
+            true => Status::Up,
+            false => Status::Down,
+        };
+        Readiness { status, shutting_down: false, checks }
+    }
+
+    async fn check(&self, name: &'static str, probe: impl Future<Output = Result<(), String>>) -> Check {
+        let start = Instant::now();
+        let result = match tokio::time::timeout(self.timeout, probe).await {
+            Ok(result) => result,
+            Err(_) => Err(format!("no answer within {} seconds", self.timeout.as_secs())),
+        };
+        if let Err(e) = &result {
+            tracing::event!(tracing::Level::WARN, check = name, "Readiness check failed: {}", e);
+        }
+        Check {
+            status: if result.is_ok() { Status::Up } else { Status::Down },
+            duration_ms: start.elapsed().as_millis() as u64,
+        }
+    }
+
+    async fn ping_database(&self) -> Result<(), String> {
+        self.store.ping().await.map_err(describe)
+    }
+
+    async fn check_migrations(&self) -> Result<(), String> {
+        let pending = self.store.pending_migrations().await.map_err(describe)?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let versions: Vec<String> = pending.iter().map(i64::to_string).collect();
+        Err(format!("{} migration(s) not applied: {}", pending.len(), versions.join(", ")))
+    }
+
+    async fn probe_profanity(&self) -> Result<(), String> {
+        self.profanity.analyze(PROBE_TEXT.to_owned()).await.map(|_| ()).map_err(describe)
+    }
+}
+
+// the `Display` of a database error is meant for clients and hides the cause
+fn describe(error: WarpError) -> String {
+    match error {
+        WarpError::DatabaseQueryError(e) => e.to_string(),
+        e => e.to_string(),
+    }
+}
+
+// `GET /health/live`
+pub async fn live() -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&serde_json::json!({ "status": Status::Up })))
+}
+
+// `GET /health/ready`; 503 while any check is down, so the instance gets no traffic
+pub async fn ready(health: HealthChecker) -> Result<impl Reply, Infallible> {
+    let readiness = health.readiness().await;
+    let status = match readiness.status {
+        Status::Up => StatusCode::OK,
+        Status::Down => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&readiness), status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use handle_errors::APILayerError;
+    use crate::profanity::{BadWordsResponse, ProfanityChecker, WordListChecker};
+    use crate::store::HealthStore;
+
+    #[derive(Debug)]
+    struct FakeStore {
+        reachable: bool,
+        pending: Vec<i64>,
+    }
+
+    #[async_trait]
+    impl HealthStore for FakeStore {
+        async fn ping(&self) -> Result<(), WarpError> {
+            match self.reachable {
+                true => Ok(()),
+                false => Err(WarpError::DatabaseQueryError(sqlx::Error::PoolTimedOut)),
+            }
+        }
+
+        async fn pending_migrations(&self) -> Result<Vec<i64>, WarpError> {
+            self.ping().await?;
+            Ok(self.pending.clone())
+        }
+    }
+
+    #[derive(Debug)]
+    struct DownChecker;
+
+    #[async_trait]
+    impl ProfanityChecker for DownChecker {
+        async fn analyze(&self, _content: String) -> Result<BadWordsResponse, WarpError> {
+            Err(WarpError::ServerError(APILayerError { status: 503, message: "Service Unavailable".to_owned() }))
+        }
+    }
+
+    fn checker(store: FakeStore, profanity: DynProfanityChecker) -> HealthChecker {
+        HealthChecker::new(Arc::new(store), profanity, &HealthConfig { probe_profanity: true, ..HealthConfig::default() })
+    }
+
+    fn healthy() -> FakeStore {
+        FakeStore { reachable: true, pending: Vec::new() }
+    }
+
+    fn statuses(readiness: &Readiness) -> Vec<(&'static str, Status)> {
+        readiness.checks.iter().map(|(name, check)| (*name, check.status)).collect()
+    }
+
+    #[tokio::test]
+    async fn ready_while_every_dependency_is_up() {
+        let readiness = checker(healthy(), Arc::new(WordListChecker::default())).readiness().await;
+        assert_eq!(readiness.status, Status::Up);
+        assert_eq!(statuses(&readiness), [("database", Status::Up), ("migrations", Status::Up), ("profanity", Status::Up)]);
+    }
+
+    #[tokio::test]
+    async fn an_unreachable_or_outdated_database_is_not_ready() {
+        let unreachable = checker(FakeStore { reachable: false, pending: Vec::new() }, Arc::new(WordListChecker::default()));
+        let readiness = unreachable.readiness().await;
+        assert_eq!(readiness.status, Status::Down);
+        assert_eq!(statuses(&readiness), [("database", Status::Down), ("migrations", Status::Down), ("profanity", Status::Up)]);
+
+        let outdated = checker(FakeStore { reachable: true, pending: vec![20231211093005] }, Arc::new(WordListChecker::default()));
+        let readiness = outdated.readiness().await;
+        assert_eq!(readiness.status, Status::Down);
+        assert_eq!(statuses(&readiness), [("database", Status::Up), ("migrations", Status::Down), ("profanity", Status::Up)]);
+    }
+
+    #[tokio::test]
+    async fn a_failing_profanity_backend_only_counts_when_probed() {
+        let readiness = checker(healthy(), Arc::new(DownChecker)).readiness().await;
+        assert_eq!(readiness.status, Status::Down);
+        assert_eq!(statuses(&readiness), [("database", Status::Up), ("migrations", Status::Up), ("profanity", Status::Down)]);
+
+        let unprobed = HealthChecker::new(Arc::new(healthy()), Arc::new(DownChecker), &HealthConfig::default());
+        let readiness = unprobed.readiness().await;
+        assert_eq!(readiness.status, Status::Up);
+        assert_eq!(statuses(&readiness), [("database", Status::Up), ("migrations", Status::Up)]);
+    }
+
+    #[tokio::test]
+    async fn the_failure_stays_out_of_the_response() {
+        let readiness = checker(FakeStore { reachable: false, pending: Vec::new() }, Arc::new(DownChecker)).readiness().await;
+        let body = serde_json::to_string(&readiness).unwrap();
+        assert!(!body.contains("pool") && !body.contains("Unavailable"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn shutting_down_is_never_ready() {
+        let health = checker(healthy(), Arc::new(WordListChecker::default()));
+        let clone = health.clone();
+        clone.start_shutdown();
+
+        // the flag is shared with the clone the route got
+        let readiness = health.readiness().await;
+        assert_eq!(readiness.status, Status::Down);
+        assert!(readiness.checks.is_empty());
+        assert_eq!(serde_json::to_value(&readiness).unwrap(), serde_json::json!({ "status": "down", "shutting_down": true, "checks": {} }));
+        let response = ready(health).await.unwrap().into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
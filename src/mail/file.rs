@@ -0,0 +1,33 @@
+use std::path::{Path, PathBuf};
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+
+use crate::mail::{build_message, Mail, MailError, Mailer};
+
+// writes every email as an `.eml` file, which mail clients and mail-catchers can open
+#[derive(Debug)]
+pub struct FileMailer {
+    dir: PathBuf,
+    from: Mailbox,
+}
+
+impl FileMailer {
+    pub fn new(dir: &Path, from: Mailbox) -> Result<Self, MailError> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let message = build_message(&self.from, mail)?;
+        let path = self.dir.join(format!("{}.eml", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, message.formatted()).await?;
+        tracing::event!(tracing::Level::INFO, path = %path.display(), "email written");
+        Ok(())
+    }
+}
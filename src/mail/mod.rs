@@ -0,0 +1,63 @@
+// outgoing email; the transport is picked through `mail.transport`
+use std::fmt::Debug;
+use std::sync::Arc;
+use async_trait::async_trait;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::Message;
+
+use crate::config::{MailConfig, MailTransport};
+
+pub mod smtp;
+pub mod file;
+pub mod stdout;
+
+pub use smtp::SmtpMailer;
+pub use file::FileMailer;
+pub use stdout::StdoutMailer;
+
+pub type MailError = Box<dyn std::error::Error + Send + Sync>;
+
+// a plain text email; the sender is part of the mailer's configuration
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync + Debug {
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
+
+pub type DynMailer = Arc<dyn Mailer>;
+
+pub fn from_config(config: &MailConfig) -> Result<DynMailer, MailError> {
+    let from: Mailbox = config.from.parse()?;
+    Ok(match config.transport {
+        Some(MailTransport::Smtp) => Arc::new(SmtpMailer::new(config, from)?),
+        Some(MailTransport::File) => Arc::new(FileMailer::new(&config.file_dir, from)?),
+        Some(MailTransport::Stdout) => Arc::new(StdoutMailer::new(from)),
+        None => return Err("mail.transport is not set".into()),
+    })
+}
+
+// sends after the response went out, so a slow mail server doesn't hold up the request and the
+// response time doesn't tell whether an account exists
+pub fn send_in_background(mailer: DynMailer, mail: Mail) {
+    tokio::spawn(async move {
+        let subject = mail.subject.clone();
+        if let Err(e) = mailer.send(mail).await {
+            tracing::event!(tracing::Level::ERROR, subject = %subject, "Could not send email: {}", e);
+        }
+    });
+}
+
+fn build_message(from: &Mailbox, mail: Mail) -> Result<Message, MailError> {
+    Ok(Message::builder()
+        .from(from.clone())
+        .to(mail.to.parse()?)
+        .subject(mail.subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(mail.body)?)
+}
@@ -0,0 +1,25 @@
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+
+use crate::mail::{build_message, Mail, MailError, Mailer};
+
+// prints every email, tokens included; only for local development
+#[derive(Debug)]
+pub struct StdoutMailer {
+    from: Mailbox,
+}
+
+impl StdoutMailer {
+    pub fn new(from: Mailbox) -> Self {
+        Self { from }
+    }
+}
+
+#[async_trait]
+impl Mailer for StdoutMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let message = build_message(&self.from, mail)?;
+        println!("{}\n", String::from_utf8_lossy(&message.formatted()));
+        Ok(())
+    }
+}
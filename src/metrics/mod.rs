@@ -0,0 +1,269 @@
+// Prometheus metrics served on `GET /metrics`: HTTP traffic, storage latency, the Postgres pool
+// and calls to the profanity API
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use sqlx::PgPool;
+use warp::http::header::CONTENT_TYPE;
+use warp::{Rejection, Reply};
+use handle_errors::WarpError; // internal library
+
+use crate::config::MetricsConfig;
+
+// every static path segment of `routes::router`; paths with any other segment are counted as
+// `unmatched`, so arbitrary URLs can't create new time series
+const ROUTE_SEGMENTS: [&str; 29] = [
+    "accept", "account", "accounts", "admin", "answers", "diff", "email", "export", "forgot", "health",
+    "live", "login", "logout", "logout-all", "metrics", "password", "questions", "ready", "refresh",
+    "registration", "resend", "reset", "restore", "revisions", "role", "rollback", "search", "verify",
+    "vote",
+];
+// no route has more segments than this
+const MAX_ROUTE_SEGMENTS: usize = 5;
+// in seconds; from a cached lookup to a slow search or a stalled pool
+const LATENCY_BUCKETS: &[f64] = &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// All metrics of the server, registered in their own registry; cheap to clone
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    store_duration: HistogramVec,
+    profanity_requests: IntCounterVec,
+    profanity_retries: IntCounter,
+    // what a scrape has to authenticate with; `None` serves the metrics to anyone
+    bearer_token: Option<Arc<str>>,
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route, method and response status"),
+            &["route", "method", "status"],
+        ).expect("valid metric");
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Time until the response was ready, by route and method")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+            &["route", "method"],
+        ).expect("valid metric");
+        let store_duration = HistogramVec::new(
+            HistogramOpts::new("store_operation_duration_seconds", "Duration of every storage backend call, by method and outcome")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+            &["operation", "outcome"],
+        ).expect("valid metric");
+        let profanity_requests = IntCounterVec::new(
+            Opts::new("profanity_api_requests_total", "Profanity API checks by outcome, after all retries"),
+            &["outcome"],
+        ).expect("valid metric");
+        let profanity_retries = IntCounter::new("profanity_api_retries_total", "Repeated attempts to reach the profanity API")
+            .expect("valid metric");
+
+        for collector in [
+            Box::new(http_requests.clone()) as Box<dyn Collector>,
+            Box::new(http_duration.clone()),
+            Box::new(store_duration.clone()),
+            Box::new(profanity_requests.clone()),
+            Box::new(profanity_retries.clone()),
+        ] {
+            registry.register(collector).expect("metric names are unique");
+        }
+        Self {
+            registry,
+            http_requests,
+            http_duration,
+            store_duration,
+            profanity_requests,
+            profanity_retries,
+            bearer_token: None,
+        }
+    }
+
+    pub fn from_config(config: &MetricsConfig) -> Self {
+        Self {
+            bearer_token: config.bearer_token.as_deref().map(Arc::from),
+            ..Self::new()
+        }
+    }
+
+    pub fn requires_token(&self) -> bool {
+        self.bearer_token.is_some()
+    }
+
+    // compares in constant time, so the token can't be guessed byte by byte from response times
+    fn authorized(&self, authorization: Option<&str>) -> bool {
+        let Some(expected) = &self.bearer_token else { return true };
+        let Some(token) = authorization.and_then(|value| value.strip_prefix("Bearer ")) else { return false };
+        token.len() == expected.len()
+            && token.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+
+    pub fn observe_request(&self, method: &str, path: &str, status: u16, elapsed: Duration) {
+        let route = route_label(path);
+        let method = method_label(method);
+        self.http_requests.with_label_values(&[&route, method, &status.to_string()]).inc();
+        self.http_duration.with_label_values(&[&route, method]).observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_store(&self, operation: &str, succeeded: bool, elapsed: Duration) {
+        let outcome = if succeeded { "ok" } else { "error" };
+        self.store_duration.with_label_values(&[operation, outcome]).observe(elapsed.as_secs_f64());
+    }
+
+    pub fn profanity_request(&self, outcome: &str) {
+        self.profanity_requests.with_label_values(&[outcome]).inc();
+    }
+
+    pub fn profanity_retry(&self) {
+        self.profanity_retries.inc();
+    }
+
+    /// Reports the connections of `pool` on every scrape; sqlx doesn't tell the pool's size
+    /// limit, so it comes from the config
+    pub fn watch_pool(&self, pool: PgPool, max_connections: u32) {
+        if let Err(e) = self.registry.register(Box::new(PoolCollector::new(pool, max_connections))) {
+            tracing::event!(tracing::Level::ERROR, "Could not register the pool metrics: {}", e);
+        }
+    }
+
+    fn render(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+// a scrape reads the pool as it is at that moment; `in_use` reaching `max` means requests queue
+// for a connection
+struct PoolCollector {
+    pool: PgPool,
+    connections: IntGaugeVec,
+    max_connections: IntGauge,
+}
+
+impl PoolCollector {
+    fn new(pool: PgPool, max: u32) -> Self {
+        let connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Open Postgres connections by state"),
+            &["state"],
+        ).expect("valid metric");
+        let max_connections = IntGauge::new("db_pool_max_connections", "Size limit of the Postgres pool")
+            .expect("valid metric");
+        max_connections.set(max as i64);
+        Self { pool, connections, max_connections }
+    }
+}
+
+impl Collector for PoolCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.connections.desc().into_iter().chain(self.max_connections.desc()).collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let size = self.pool.size() as i64;
+        let idle = self.pool.num_idle() as i64;
+        self.connections.with_label_values(&["idle"]).set(idle);
+        self.connections.with_label_values(&["in_use"]).set(size - idle);
+        self.connections.collect().into_iter().chain(self.max_connections.collect()).collect()
+    }
+}
+
+// `/questions/12/revisions/3` becomes `/questions/{id}/revisions/{id}`
+pub fn route_label(path: &str) -> String {
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    if segments.len() > MAX_ROUTE_SEGMENTS {
+        return "unmatched".to_owned();
+    }
+    let mut route = String::new();
+    for segment in segments {
+        if segment.bytes().all(|b| b.is_ascii_digit()) {
+            route.push_str("/{id}");
+        } else if ROUTE_SEGMENTS.contains(&segment) {
+            route.push('/');
+            route.push_str(segment);
+        } else {
+            return "unmatched".to_owned();
+        }
+    }
+    if route.is_empty() {
+        route.push('/');
+    }
+    route
+}
+
+// clients may send any token as the method, so only the standard ones get a label of their own
+pub fn method_label(method: &str) -> &str {
+    match method {
+        "GET" | "HEAD" | "POST" | "PUT" | "DELETE" | "CONNECT" | "OPTIONS" | "TRACE" | "PATCH" => method,
+        _ => "other",
+    }
+}
+
+// logs every response into the HTTP metrics, whichever route (or rejection) produced it
+pub fn log(metrics: Metrics) -> warp::log::Log<impl Fn(warp::log::Info<'_>) + Clone> {
+    warp::log::custom(move |info| {
+        metrics.observe_request(info.method().as_str(), info.path(), info.status().as_u16(), info.elapsed());
+    })
+}
+
+// `GET /metrics`, in the Prometheus text format
+pub async fn serve(authorization: Option<String>, metrics: Metrics) -> Result<impl Reply, Rejection> {
+    if !metrics.authorized(authorization.as_deref()) {
+        return Err(warp::reject::custom(WarpError::InvalidMetricsToken));
+    }
+    let response = match metrics.render() {
+        Ok(body) => warp::reply::with_status(body, warp::http::StatusCode::OK),
+        Err(e) => {
+            tracing::event!(tracing::Level::ERROR, "Could not encode the metrics: {}", e);
+            warp::reply::with_status(String::new(), warp::http::StatusCode::INTERNAL_SERVER_ERROR)
+        },
+    };
+    Ok(warp::reply::with_header(response, CONTENT_TYPE, prometheus::TEXT_FORMAT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrapes_need_the_configured_token() {
+        let metrics = Metrics::from_config(&MetricsConfig { bearer_token: Some("s3cret".to_owned()) });
+        assert!(metrics.authorized(Some("Bearer s3cret")));
+        assert!(!metrics.authorized(Some("Bearer s3cre")));
+        assert!(!metrics.authorized(Some("Bearer s3cret2")));
+        assert!(!metrics.authorized(Some("s3cret")));
+        assert!(!metrics.authorized(None));
+    }
+
+    #[test]
+    fn without_a_token_anyone_may_scrape() {
+        assert!(Metrics::new().authorized(None));
+    }
+
+    #[test]
+    fn unknown_methods_share_one_label() {
+        let metrics = Metrics::new();
+        for method in ["GET", "BREW", "get", "X-SPAM-1"] {
+            metrics.observe_request(method, "/questions", 200, Duration::from_millis(5));
+        }
+        let rendered = metrics.render().unwrap();
+        assert!(rendered.contains(r#"http_requests_total{method="GET",route="/questions",status="200"} 1"#), "{}", rendered);
+        assert!(rendered.contains(r#"http_requests_total{method="other",route="/questions",status="200"} 3"#), "{}", rendered);
+    }
+}
@@ -0,0 +1,124 @@
+// best practice to receive API data through a `struct`
+use serde::{Deserialize, Serialize};
+use async_trait::async_trait;
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Middleware, Next};
+use reqwest_retry::{RetryTransientMiddleware, policies::ExponentialBackoff};
+use task_local_extensions::Extensions;
+
+use crate::config::ProfanityConfig;
+use crate::metrics::Metrics;
+use crate::profanity::{BadWordsResponse, ProfanityChecker};
+use crate::telemetry::TracePropagation;
+
+// how much of an unexpected error body ends up in the logs
+const MAX_ERROR_BODY: usize = 200;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct APIResponse {
+    message: String
+}
+
+/// Checks content through the APILayer bad words API
+#[derive(Debug, Clone)]
+pub struct ApiLayerChecker {
+    client: ClientWithMiddleware,
+    api_url: String,
+    api_key: String,
+    metrics: Metrics,
+}
+
+impl ApiLayerChecker {
+    pub fn new(config: &ProfanityConfig, metrics: Metrics) -> Self {
+        // retry communicating with the API incase of initial failure
+        let retry_policy = ExponentialBackoff::builder().build_with_max_retries(config.max_retries);
+        // added after the retry middleware, so they see every single attempt
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+            .with(RetryCounter { metrics: metrics.clone() })
+            .with(TracePropagation)
+            .build();
+        Self {
+            client,
+            api_url: config.api_url.clone(),
+            api_key: config.api_key.clone(),
+            metrics,
+        }
+    }
+
+    async fn request(&self, content: String) -> Result<BadWordsResponse, handle_errors::WarpError> {
+        let res = self.client
+            .post(&self.api_url)
+            .header("apikey", &self.api_key)
+            .body(content)
+            .send()
+            .await
+            .map_err(handle_errors::WarpError::MiddlewareReqwestAPIError)?;
+
+        // handle error, if client or server returns an error
+        // error from handling `adding the question` is handled seperately
+        if !res.status().is_success() {
+            if res.status().is_client_error() {
+                let err = transform_error(res).await;
+                return Err(handle_errors::WarpError::ClientError(err));
+            } else {
+                let err = transform_error(res).await;
+                return Err(handle_errors::WarpError::ServerError(err));
+            }
+        }
+
+        match res.json::<BadWordsResponse>().await {
+            Ok(res) => Ok(res),
+            Err(e) => Err(handle_errors::WarpError::ReqwestAPIError(e))
+        }
+    }
+}
+
+#[async_trait]
+impl ProfanityChecker for ApiLayerChecker {
+    async fn analyze(&self, content: String) -> Result<BadWordsResponse, handle_errors::WarpError> {
+        let result = self.request(content).await;
+        self.metrics.profanity_request(match &result {
+            Ok(_) => "ok",
+            Err(handle_errors::WarpError::ClientError(_)) => "client_error",
+            Err(handle_errors::WarpError::ServerError(_)) => "server_error",
+            Err(handle_errors::WarpError::MiddlewareReqwestAPIError(_)) => "unreachable",
+            Err(_) => "invalid_response",
+        });
+        result
+    }
+}
+
+// the extensions live as long as the call, across all of its attempts
+struct Attempted;
+
+struct RetryCounter {
+    metrics: Metrics,
+}
+
+#[async_trait]
+impl Middleware for RetryCounter {
+    async fn handle(&self, req: reqwest::Request, extensions: &mut Extensions, next: Next<'_>) -> reqwest_middleware::Result<reqwest::Response> {
+        if extensions.get::<Attempted>().is_some() {
+            self.metrics.profanity_retry();
+        } else {
+            extensions.insert(Attempted);
+        }
+        next.run(req, extensions).await
+    }
+}
+
+// proxies and gateways in front of the API answer with HTML or nothing at all, so the JSON
+// message is only taken when there is one; otherwise the raw body, or the status reason
+async fn transform_error(res: reqwest::Response) -> handle_errors::APILayerError {
+    let status = res.status();
+    let body = res.text().await.unwrap_or_default();
+    let message = match serde_json::from_str::<APIResponse>(&body) {
+        Ok(response) => response.message,
+        Err(_) if !body.trim().is_empty() => body.trim().chars().take(MAX_ERROR_BODY).collect(),
+        Err(_) => status.canonical_reason().unwrap_or("no message").to_owned(),
+    };
+    handle_errors::APILayerError {
+        status: status.as_u16(),
+        message,
+    }
+}
@@ -0,0 +1,206 @@
+// offline profanity filter working on a plain word list; keeps writes working when
+// APILayer cannot be reached
+use std::path::Path;
+use std::sync::Arc;
+use async_trait::async_trait;
+
+use crate::profanity::{BadWord, BadWordsResponse, ProfanityChecker};
+
+const DEFAULT_WORD_LIST: &str = include_str!("words.txt");
+const CENSOR_CHARACTER: char = '*';
+
+// `info` values of a reported `BadWord`
+const EXACT_MATCH: i64 = 0;
+const OBFUSCATED_MATCH: i64 = 1;
+
+#[derive(Debug)]
+struct ListEntry {
+    word: String,
+    // run-length encoding of the word, e.g. "ass" -> [('a', 1), ('s', 2)]
+    runs: Vec<(char, usize)>,
+}
+
+/// Censors whole words from a word list, seeing through leetspeak ("sh1t", "$hit")
+/// and stretched words ("shiiiit")
+#[derive(Debug, Clone)]
+pub struct WordListChecker {
+    entries: Arc<Vec<ListEntry>>,
+}
+
+impl Default for WordListChecker {
+    fn default() -> Self {
+        Self::from_list(DEFAULT_WORD_LIST)
+    }
+}
+
+impl WordListChecker {
+    /// One word per line; empty lines and lines starting with `#` are skipped
+    pub fn from_list(list: &str) -> Self {
+        let entries = list.lines()
+            .map(|line| line.trim().to_lowercase())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|word| ListEntry { runs: runs(word.chars()), word })
+            .collect();
+        Self { entries: Arc::new(entries) }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, std::io::Error> {
+        Ok(Self::from_list(&std::fs::read_to_string(path)?))
+    }
+
+    fn find(&self, token: &str) -> Option<BadWord> {
+        let lowercase: Vec<char> = token.chars().flat_map(char::to_lowercase).collect();
+        let normalized: Vec<char> = lowercase.iter().map(|&c| unleet(c)).collect();
+        let token_runs = runs(normalized.iter().copied());
+
+        let entry = self.entries.iter().find(|entry| matches(&token_runs, &entry.runs))?;
+        let substitutions = lowercase.iter().zip(&normalized).filter(|(a, b)| a != b).count();
+        let repetitions = normalized.len() - entry.word.chars().count();
+        let deviations = (substitutions + repetitions) as i64;
+        Some(BadWord {
+            original: token.to_owned(),
+            word: entry.word.clone(),
+            deviations,
+            info: if deviations == 0 { EXACT_MATCH } else { OBFUSCATED_MATCH },
+            replaced_len: token.chars().count() as i64,
+        })
+    }
+}
+
+#[async_trait]
+impl ProfanityChecker for WordListChecker {
+    async fn analyze(&self, content: String) -> Result<BadWordsResponse, handle_errors::WarpError> {
+        let mut bad_words_list = Vec::new();
+        let mut censored_content = String::with_capacity(content.len());
+        let mut last_end = 0;
+
+        for (start, end) in tokens(&content) {
+            if let Some(bad_word) = self.find(&content[start..end]) {
+                censored_content.push_str(&content[last_end..start]);
+                censored_content.extend(std::iter::repeat_n(CENSOR_CHARACTER, bad_word.replaced_len as usize));
+                last_end = end;
+                bad_words_list.push(bad_word);
+            }
+        }
+        censored_content.push_str(&content[last_end..]);
+
+        Ok(BadWordsResponse {
+            bad_words_total: bad_words_list.len() as i64,
+            bad_words_list,
+            censored_content,
+            content,
+        })
+    }
+}
+
+// characters commonly swapped in for letters
+fn unleet(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' | '!' | '|' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' | '+' => 't',
+        '8' => 'b',
+        '9' => 'g',
+        c => c,
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '!' | '|' | '@' | '$' | '+')
+}
+
+// byte ranges of the words in `content`; a trailing `!` is punctuation, not a letter
+fn tokens(content: &str) -> Vec<(usize, usize)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in content.char_indices().chain(std::iter::once((content.len(), ' '))) {
+        match (start, is_word_char(c)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                let end = s + content[s..i].trim_end_matches('!').len();
+                if end > s {
+                    tokens.push((s, end));
+                }
+                start = None;
+            },
+            _ => (),
+        }
+    }
+    tokens
+}
+
+fn runs(chars: impl Iterator<Item = char>) -> Vec<(char, usize)> {
+    let mut runs: Vec<(char, usize)> = Vec::new();
+    for c in chars {
+        match runs.last_mut() {
+            Some((last, count)) if *last == c => *count += 1,
+            _ => runs.push((c, 1)),
+        }
+    }
+    runs
+}
+
+// same letters in the same order, each repeated at least as often as in the word
+fn matches(token: &[(char, usize)], word: &[(char, usize)]) -> bool {
+    token.len() == word.len()
+        && token.iter().zip(word).all(|((a, n), (b, m))| a == b && n >= m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn censor(content: &str) -> BadWordsResponse {
+        WordListChecker::from_list("# comment\n\nshit\nass\n").analyze(content.to_owned()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn censors_exact_matches_and_keeps_the_rest() {
+        let res = censor("Oh shit, that is it.").await;
+        assert_eq!(res.censored_content, "Oh ****, that is it.");
+        assert_eq!(res.bad_words_total, 1);
+        assert_eq!(res.bad_words_list[0].word, "shit");
+        assert_eq!(res.bad_words_list[0].deviations, 0);
+        assert_eq!(res.bad_words_list[0].info, EXACT_MATCH);
+    }
+
+    #[tokio::test]
+    async fn sees_through_leetspeak() {
+        let res = censor("sh1t $hit 5h!t").await;
+        assert_eq!(res.censored_content, "**** **** ****");
+        assert!(res.bad_words_list.iter().all(|bad_word| bad_word.word == "shit" && bad_word.info == OBFUSCATED_MATCH));
+        assert_eq!(res.bad_words_list[2].deviations, 2);
+    }
+
+    #[tokio::test]
+    async fn sees_through_stretched_words() {
+        let res = censor("SHIIIIT and asssss").await;
+        assert_eq!(res.censored_content, "******* and ******");
+        assert_eq!(res.bad_words_list[0].deviations, 3);
+        assert_eq!(res.bad_words_list[1].word, "ass");
+    }
+
+    #[tokio::test]
+    async fn leaves_longer_words_and_missing_runs_alone() {
+        // "as" lacks a run of the word, "class" and "shitake" are other words
+        let res = censor("as class shitake").await;
+        assert_eq!(res.bad_words_total, 0);
+        assert_eq!(res.censored_content, "as class shitake");
+    }
+
+    #[tokio::test]
+    async fn keeps_trailing_exclamation_marks() {
+        let res = censor("shit!!").await;
+        assert_eq!(res.censored_content, "****!!");
+    }
+
+    #[test]
+    fn encodes_runs() {
+        assert_eq!(runs("assess".chars()), vec![('a', 1), ('s', 2), ('e', 1), ('s', 2)]);
+        assert!(matches(&runs("asss".chars()), &runs("ass".chars())));
+        assert!(!matches(&runs("as".chars()), &runs("ass".chars())));
+    }
+}
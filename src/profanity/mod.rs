@@ -0,0 +1,57 @@
+// profanity filtering for user content; the backend is picked through `profanity.backend`
+use std::fmt::Debug;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use async_trait::async_trait;
+
+use crate::config::{ProfanityBackend, ProfanityConfig};
+use crate::metrics::Metrics;
+
+pub mod apilayer;
+pub mod local;
+
+pub use apilayer::ApiLayerChecker;
+pub use local::WordListChecker;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BadWord {
+    // the text as it appeared in the content
+    pub original: String,
+    // the list entry it matched
+    pub word: String,
+    pub deviations: i64,
+    pub info: i64,
+    #[serde(rename = "replacedLen")]
+    pub replaced_len: i64,
+}
+
+// the APILayer response; every backend reports in this shape
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BadWordsResponse {
+    pub content: String,
+    pub bad_words_total: i64,
+    pub bad_words_list: Vec<BadWord>,
+    pub censored_content: String,
+}
+
+#[async_trait]
+pub trait ProfanityChecker: Send + Sync + Debug {
+    async fn analyze(&self, content: String) -> Result<BadWordsResponse, handle_errors::WarpError>;
+}
+
+pub type DynProfanityChecker = Arc<dyn ProfanityChecker>;
+
+pub fn from_config(config: &ProfanityConfig, metrics: &Metrics) -> Result<DynProfanityChecker, std::io::Error> {
+    Ok(match config.backend {
+        ProfanityBackend::ApiLayer => Arc::new(ApiLayerChecker::new(config, metrics.clone())),
+        ProfanityBackend::Local => match &config.word_list {
+            Some(path) => Arc::new(WordListChecker::from_file(path)?),
+            None => Arc::new(WordListChecker::default()),
+        },
+    })
+}
+
+// filter out bad words from `String` passed in.
+pub async fn check_profanity(checker: &dyn ProfanityChecker, content: String) -> Result<String, handle_errors::WarpError> {
+    checker.analyze(content).await.map(|res| res.censored_content)
+}
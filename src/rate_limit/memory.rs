@@ -0,0 +1,169 @@
+// counters of a single instance; lost on restart, which at worst lifts a lock early
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+
+use crate::config::BucketConfig;
+use crate::rate_limit::RateLimitStore;
+
+// above this many entries, entries that carry no information anymore are dropped
+const PRUNE_THRESHOLD: usize = 10_000;
+// a map that stays above the threshold with live entries is swept this often at most, not on
+// every request
+const PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+struct Entries<V> {
+    map: HashMap<String, V>,
+    pruned_at: Option<Instant>,
+}
+
+impl<V> Default for Entries<V> {
+    fn default() -> Self {
+        Self { map: HashMap::new(), pruned_at: None }
+    }
+}
+
+impl<V> Entries<V> {
+    // keeps the entries for which `keep` holds
+    fn prune(&mut self, now: Instant, mut keep: impl FnMut(&V) -> bool) {
+        if self.map.len() < PRUNE_THRESHOLD || self.pruned_at.is_some_and(|at| now < at + PRUNE_INTERVAL) {
+            return;
+        }
+        self.map.retain(|_, stored| keep(stored));
+        self.pruned_at = Some(now);
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    // from then on the bucket is as good as a new one
+    full_at: Instant,
+}
+
+#[derive(Debug)]
+struct Lockout {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+    window: Duration,
+}
+
+impl Lockout {
+    fn expired(&self, now: Instant) -> bool {
+        let last_event = self.locked_until.map_or(self.last_failure, |until| until.max(self.last_failure));
+        now > last_event + self.window
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct InProcessStore {
+    buckets: Mutex<Entries<Bucket>>,
+    lockouts: Mutex<Entries<Lockout>>,
+}
+
+#[async_trait]
+impl RateLimitStore for InProcessStore {
+    async fn take(&self, key: &str, bucket: &BucketConfig) -> Option<Duration> {
+        let now = Instant::now();
+        let capacity = bucket.capacity as f64;
+        let per_second = bucket.per_minute as f64 / 60.0;
+        let mut buckets = lock(&self.buckets);
+        buckets.prune(now, |stored| stored.full_at > now);
+        let stored = buckets.map.entry(key.to_owned()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+            full_at: now,
+        });
+        let tokens = (stored.tokens + now.duration_since(stored.updated).as_secs_f64() * per_second).min(capacity);
+        let wait = if tokens >= 1.0 {
+            stored.tokens = tokens - 1.0;
+            None
+        } else {
+            stored.tokens = tokens;
+            Some(Duration::from_secs_f64((1.0 - tokens) / per_second))
+        };
+        stored.updated = now;
+        stored.full_at = now + Duration::from_secs_f64((capacity - stored.tokens) / per_second);
+        wait
+    }
+
+    async fn add_failure(&self, key: &str, window: Duration) -> u32 {
+        let now = Instant::now();
+        let mut lockouts = lock(&self.lockouts);
+        lockouts.prune(now, |stored| !stored.expired(now));
+        let stored = lockouts.map.entry(key.to_owned()).or_insert(Lockout {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+            window,
+        });
+        if stored.expired(now) {
+            stored.failures = 0;
+            stored.locked_until = None;
+        }
+        stored.failures += 1;
+        stored.last_failure = now;
+        stored.window = window;
+        stored.failures
+    }
+
+    async fn lock(&self, key: &str, duration: Duration) {
+        let now = Instant::now();
+        let mut lockouts = lock(&self.lockouts);
+        let stored = lockouts.map.entry(key.to_owned()).or_insert(Lockout {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+            window: Duration::ZERO,
+        });
+        stored.locked_until = Some(now + duration);
+    }
+
+    async fn locked_for(&self, key: &str) -> Option<Duration> {
+        let now = Instant::now();
+        lock(&self.lockouts).map.get(key)
+            .and_then(|stored| stored.locked_until)
+            .and_then(|until| until.checked_duration_since(now))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    async fn reset(&self, key: &str) {
+        lock(&self.lockouts).map.remove(key);
+    }
+}
+
+// a poisoned lock only means another request panicked half-way; the counters are still usable
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_maps_are_swept_once_per_interval() {
+        let start = Instant::now();
+        let mut entries = Entries::default();
+        for i in 0..PRUNE_THRESHOLD {
+            entries.map.insert(i.to_string(), i);
+        }
+        let mut sweeps = 0;
+        entries.prune(start, |_| { sweeps += 1; true });
+        assert_eq!(sweeps, PRUNE_THRESHOLD);
+
+        // every entry is still live, and the next requests don't go through all of them again
+        entries.prune(start + Duration::from_secs(1), |_| { sweeps += 1; true });
+        assert_eq!(sweeps, PRUNE_THRESHOLD);
+
+        entries.prune(start + PRUNE_INTERVAL, |i| i % 2 == 0);
+        assert_eq!(entries.map.len(), PRUNE_THRESHOLD / 2);
+        // below the threshold nothing is swept at all
+        entries.prune(start + 3 * PRUNE_INTERVAL, |_| false);
+        assert_eq!(entries.map.len(), PRUNE_THRESHOLD / 2);
+    }
+}
@@ -0,0 +1,239 @@
+// throttling of logins, registrations and new content: token buckets per client address and
+// per account, and a growing lockout after repeated wrong passwords
+use std::fmt::Debug;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use warp::{Filter, Rejection};
+use handle_errors::WarpError; // internal library
+
+use crate::config::{BucketConfig, RateLimitConfig};
+use crate::types::account::{AccountId, Session};
+
+pub mod memory;
+
+pub use memory::InProcessStore;
+
+/// Where the limiter keeps its counters. `InProcessStore` only sees the requests of its own
+/// instance; several instances behind a load balancer need a shared implementation
+#[async_trait]
+pub trait RateLimitStore: Send + Sync + Debug {
+    // takes one token out of the bucket at `key`; if it is empty, returns how long until the next one
+    async fn take(&self, key: &str, bucket: &BucketConfig) -> Option<Duration>;
+    // counts a failure at `key` and returns the failures in a row; the count starts over once
+    // `window` passes after the last failure (or the end of the lock) without a new one
+    async fn add_failure(&self, key: &str, window: Duration) -> u32;
+    async fn lock(&self, key: &str, duration: Duration);
+    // how much longer `key` stays locked
+    async fn locked_for(&self, key: &str) -> Option<Duration>;
+    // forgets the failures and the lock at `key`
+    async fn reset(&self, key: &str);
+}
+
+pub type DynRateLimitStore = Arc<dyn RateLimitStore>;
+
+/// Applies the configured limits; cheap to clone into every route
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    store: DynRateLimitStore,
+    config: Arc<RateLimitConfig>,
+}
+
+impl RateLimiter {
+    pub fn new(store: DynRateLimitStore, config: &RateLimitConfig) -> Self {
+        Self {
+            store,
+            config: Arc::new(config.clone()),
+        }
+    }
+
+    pub fn from_config(config: &RateLimitConfig) -> Self {
+        Self::new(Arc::new(InProcessStore::default()), config)
+    }
+
+    async fn check_ip(&self, scope: &str, ip: IpAddr) -> Result<(), WarpError> {
+        match self.store.take(&format!("ip:{}:{}", scope, ip), &self.config.ip).await {
+            Some(wait) => Err(WarpError::TooManyRequests(retry_after(wait))),
+            None => Ok(()),
+        }
+    }
+
+    async fn check_account(&self, scope: &str, account_id: &AccountId) -> Result<(), WarpError> {
+        match self.store.take(&format!("account:{}:{}", scope, account_id.0), &self.config.account).await {
+            Some(wait) => Err(WarpError::TooManyRequests(retry_after(wait))),
+            None => Ok(()),
+        }
+    }
+
+    /// Rejects logins to a locked account before the password is even checked
+    pub async fn check_login(&self, email: &str) -> Result<(), WarpError> {
+        match self.store.locked_for(&lockout_key(email)).await {
+            Some(wait) => Err(WarpError::AccountLocked(retry_after(wait))),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn login_failed(&self, email: &str) {
+        let lockout = &self.config.lockout;
+        let key = lockout_key(email);
+        let failures = self.store.add_failure(&key, Duration::from_secs(lockout.window)).await;
+        if failures >= lockout.max_failures {
+            let doublings = (failures - lockout.max_failures).min(32);
+            let secs = lockout.base.saturating_mul(1 << doublings).min(lockout.max);
+            tracing::event!(tracing::Level::WARN, failures, lock_secs = secs, "Account locked after repeated wrong passwords");
+            self.store.lock(&key, Duration::from_secs(secs)).await;
+        }
+    }
+
+    pub async fn login_succeeded(&self, email: &str) {
+        self.store.reset(&lockout_key(email)).await;
+    }
+}
+
+// `scope` keeps separate buckets per group of routes, so e.g. registrations don't use up logins
+pub fn per_ip(limiter: RateLimiter, scope: &'static str) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    client_ip(limiter.config.trust_forwarded_for, limiter.config.trusted_proxies)
+        .and_then(move |ip: Option<IpAddr>| {
+            let limiter = limiter.clone();
+            async move {
+                // only requests that never went through a socket, like `warp::test`, have no address
+                if let Some(ip) = ip {
+                    limiter.check_ip(scope, ip).await.map_err(warp::reject::custom)?;
+                }
+                Ok::<_, Rejection>(())
+            }
+        })
+        .untuple_one()
+}
+
+// wraps an authentication filter like `auth()`, so the bucket belongs to the logged in account
+pub fn per_account<F>(auth: F, limiter: RateLimiter, scope: &'static str) -> impl Filter<Extract = (Session,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (Session,), Error = Rejection> + Clone,
+{
+    auth.and_then(move |session: Session| {
+        let limiter = limiter.clone();
+        async move {
+            limiter.check_account(scope, &session.account_id).await.map_err(warp::reject::custom)?;
+            Ok::<_, Rejection>(session)
+        }
+    })
+}
+
+fn client_ip(trust_forwarded_for: bool, trusted_proxies: usize) -> impl Filter<Extract = (Option<IpAddr>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-forwarded-for")
+        .and(warp::addr::remote())
+        .map(move |forwarded: Option<String>, remote: Option<SocketAddr>| {
+            forwarded
+                .filter(|_| trust_forwarded_for)
+                .and_then(|forwarded| forwarded_client(&forwarded, trusted_proxies))
+                .or(remote.map(|remote| remote.ip()))
+        })
+}
+
+// every proxy appends the address it got the request from, so only the last `trusted_proxies`
+// entries were written by our own proxies; the leftmost of those is the client. A shorter
+// header means a proxy didn't append, and the socket address is used instead
+fn forwarded_client(forwarded: &str, trusted_proxies: usize) -> Option<IpAddr> {
+    let entries: Vec<&str> = forwarded.split(',').map(str::trim).collect();
+    let index = entries.len().checked_sub(trusted_proxies)?;
+    entries.get(index)?.parse().ok()
+}
+
+// emails are case-insensitive, so are their lockouts
+fn lockout_key(email: &str) -> String {
+    format!("login:{}", email.trim().to_lowercase())
+}
+
+// whole seconds, rounded up, so clients never retry too early
+fn retry_after(wait: Duration) -> u64 {
+    (wait.as_secs() + u64::from(wait.subsec_nanos() > 0)).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LockoutConfig;
+
+    fn limiter(lockout: LockoutConfig) -> RateLimiter {
+        RateLimiter::from_config(&RateLimitConfig { lockout, ..RateLimitConfig::default() })
+    }
+
+    async fn fail(limiter: &RateLimiter, email: &str, times: u32) {
+        for _ in 0..times {
+            limiter.login_failed(email).await;
+        }
+    }
+
+    async fn locked_secs(limiter: &RateLimiter, email: &str) -> Option<u64> {
+        match limiter.check_login(email).await {
+            Ok(()) => None,
+            Err(WarpError::AccountLocked(secs)) => Some(secs),
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn lock_doubles_with_every_further_failure_up_to_max() {
+        let limiter = limiter(LockoutConfig { max_failures: 3, base: 10, max: 35, window: 60 });
+        fail(&limiter, "jane@example.com", 2).await;
+        assert_eq!(locked_secs(&limiter, "jane@example.com").await, None);
+        fail(&limiter, "jane@example.com", 1).await;
+        assert_eq!(locked_secs(&limiter, "jane@example.com").await, Some(10));
+        fail(&limiter, "jane@example.com", 1).await;
+        assert_eq!(locked_secs(&limiter, "jane@example.com").await, Some(20));
+        fail(&limiter, "jane@example.com", 1).await;
+        assert_eq!(locked_secs(&limiter, "jane@example.com").await, Some(35));
+        // far beyond any shift that would overflow
+        fail(&limiter, "jane@example.com", 40).await;
+        assert_eq!(locked_secs(&limiter, "jane@example.com").await, Some(35));
+    }
+
+    #[tokio::test]
+    async fn lock_is_per_email_ignoring_case() {
+        let limiter = limiter(LockoutConfig { max_failures: 1, base: 10, max: 60, window: 60 });
+        fail(&limiter, " Jane@Example.com", 1).await;
+        assert_eq!(locked_secs(&limiter, "jane@example.com").await, Some(10));
+        assert_eq!(locked_secs(&limiter, "john@example.com").await, None);
+    }
+
+    #[tokio::test]
+    async fn successful_login_starts_over() {
+        let limiter = limiter(LockoutConfig { max_failures: 2, base: 10, max: 60, window: 60 });
+        fail(&limiter, "jane@example.com", 1).await;
+        limiter.login_succeeded("jane@example.com").await;
+        fail(&limiter, "jane@example.com", 1).await;
+        assert_eq!(locked_secs(&limiter, "jane@example.com").await, None);
+    }
+
+    #[tokio::test]
+    async fn empty_bucket_asks_to_wait_for_the_next_token() {
+        let limiter = RateLimiter::from_config(&RateLimitConfig {
+            ip: BucketConfig { capacity: 2, per_minute: 6 },
+            ..RateLimitConfig::default()
+        });
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        assert!(limiter.check_ip("login", ip).await.is_ok());
+        assert!(limiter.check_ip("login", ip).await.is_ok());
+        assert!(matches!(limiter.check_ip("login", ip).await, Err(WarpError::TooManyRequests(10))));
+        // scopes have buckets of their own
+        assert!(limiter.check_ip("registration", ip).await.is_ok());
+    }
+
+    #[test]
+    fn forwarded_client_skips_what_the_client_wrote() {
+        let forwarded = "198.51.100.7, 203.0.113.5, 10.0.0.2";
+        assert_eq!(forwarded_client(forwarded, 1), "10.0.0.2".parse().ok());
+        assert_eq!(forwarded_client(forwarded, 2), "203.0.113.5".parse().ok());
+        assert_eq!(forwarded_client(forwarded, 4), None);
+        assert_eq!(forwarded_client("unknown", 1), None);
+    }
+
+    #[test]
+    fn retry_after_rounds_up() {
+        assert_eq!(retry_after(Duration::from_millis(1)), 1);
+        assert_eq!(retry_after(Duration::from_millis(1500)), 2);
+        assert_eq!(retry_after(Duration::from_secs(3)), 3);
+    }
+}
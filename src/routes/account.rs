@@ -0,0 +1,60 @@
+// self-service for the logged in account; every handler in here sits behind `auth()`
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+use handle_errors::{FieldError, WarpError}; // internal library
+
+use crate::routes::authentication::{hash_password, verify_password};
+use crate::routes::email::AccountMailer;
+use crate::store::DynStore;
+use crate::types::account::{Account, AccountDeletion, AccountId, EmailChange, PasswordChange, Session};
+use crate::validation::{self, PasswordPolicy};
+
+// ends every session of the account, this one included
+pub async fn change_password(session: Session, store: DynStore, passwords: PasswordPolicy, change: PasswordChange) -> Result<impl Reply, Rejection> {
+    let account = check_password(&store, &session.account_id, &change.current_password).await?;
+    passwords.validate("new_password", &change.new_password, Some(&account.email))?;
+    store.set_password(&session.account_id, &hash_password(change.new_password.as_bytes())).await?;
+    store.revoke_account_sessions(&session.account_id).await?;
+    Ok(warp::reply::with_status("Password changed", StatusCode::OK))
+}
+
+// the sessions stay, but logging in again needs the new address to be verified
+pub async fn change_email(session: Session, store: DynStore, mails: AccountMailer, change: EmailChange) -> Result<impl Reply, Rejection> {
+    let email = validation::check_email("email", &change.email)
+        .map_err(|e| WarpError::ValidationError(vec![e]))?;
+    let account = check_password(&store, &session.account_id, &change.password).await?;
+    if account.email == email {
+        return Err(warp::reject::custom(WarpError::ValidationError(vec![
+            FieldError::new("email", "is already the email address of the account"),
+        ])));
+    }
+    store.set_email(&session.account_id, &email).await?;
+    mails.send_verification(&store, &session.account_id, &email).await?;
+    Ok(warp::reply::with_status("Email changed, please verify the new address", StatusCode::OK))
+}
+
+pub async fn delete_account(session: Session, store: DynStore, deletion: AccountDeletion) -> Result<impl Reply, Rejection> {
+    check_password(&store, &session.account_id, &deletion.password).await?;
+    store.delete_account(&session.account_id, deletion.content).await?;
+    store.revoke_account_sessions(&session.account_id).await?;
+    Ok(warp::reply::with_status("Account deleted", StatusCode::OK))
+}
+
+pub async fn export_account(session: Session, store: DynStore) -> Result<impl Reply, Rejection> {
+    let export = store.export_account(&session.account_id).await?;
+    Ok(warp::reply::with_header(
+        warp::reply::json(&export),
+        "content-disposition",
+        format!("attachment; filename=\"account-{}.json\"", session.account_id.0),
+    ))
+}
+
+// a stolen access token alone must not be enough to take over or delete the account
+async fn check_password(store: &DynStore, account_id: &AccountId, password: &str) -> Result<Account, WarpError> {
+    let account = store.get_account_by_id(account_id).await?;
+    match verify_password(&account.password, password.as_bytes()) {
+        Ok(true) => Ok(account),
+        Ok(false) => Err(WarpError::WrongPassword),
+        Err(e) => Err(WarpError::ArgonLibraryError(e)),
+    }
+}
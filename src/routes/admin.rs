@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use warp::{http::StatusCode, Rejection, Reply};
+use handle_errors::{FieldError, WarpError}; // internal library
+
+use crate::store::DynStore;
+use crate::types::account::{AccountContent, AccountId, RoleUpdate, Session};
+use crate::types::pagination;
+
+// every handler in here sits behind `require_role(Role::Admin)`
+
+pub async fn get_accounts(params: HashMap<String, String>, _session: Session, store: DynStore) -> Result<impl Reply, Rejection> {
+    let mut pagination = pagination::Pagination::default();
+    if !params.is_empty() {
+        pagination = pagination::get_pagination(params)?;
+    }
+    match store.get_accounts(pagination.limit, pagination.offset).await {
+        Ok(accounts) => Ok(warp::reply::json(&accounts)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn update_role(id: i32, session: Session, store: DynStore, update: RoleUpdate) -> Result<impl Reply, Rejection> {
+    let account_id = AccountId(id);
+    reject_own_account(&session, &account_id)?;
+    let account = store.set_account_role(&account_id, update.role).await?;
+    // access tokens carry the role, so log the account out everywhere for the change to apply at once
+    store.revoke_account_sessions(&account_id).await?;
+    Ok(warp::reply::json(&account))
+}
+
+pub async fn delete_account(id: i32, session: Session, store: DynStore) -> Result<impl Reply, Rejection> {
+    let account_id = AccountId(id);
+    reject_own_account(&session, &account_id)?;
+    // the content stays, without an owner; account holders can have it deleted through `DELETE /account`
+    store.delete_account(&account_id, AccountContent::Anonymize).await?;
+    store.revoke_account_sessions(&account_id).await?;
+    Ok(warp::reply::with_status(format!("Account {} deleted", id), StatusCode::OK))
+}
+
+// keeps admins from locking themselves out; another admin has to do it
+fn reject_own_account(session: &Session, account_id: &AccountId) -> Result<(), WarpError> {
+    if &session.account_id == account_id {
+        return Err(WarpError::ValidationError(vec![
+            FieldError::new("id", "admins cannot change or delete their own account"),
+        ]));
+    }
+    Ok(())
+}
@@ -1,14 +1,18 @@
+use std::collections::HashMap;
 use warp::{Rejection, reply::Reply, http::StatusCode};
 
-use crate::profanity::check_profanity;
-use crate::store::Store;
-use crate::types::answer::NewAnswer;
+use crate::profanity::{check_profanity, DynProfanityChecker};
+use crate::store::DynStore;
+use crate::types::answer::{NewAnswer, UpdateAnswer};
+use crate::types::pagination;
 use crate::types::account::Session;
 
 
-pub async fn add_answer(session: Session, store: Store, new_answer: NewAnswer) -> Result<impl Reply, Rejection> {
+pub async fn add_answer(session: Session, store: DynStore, profanity: DynProfanityChecker, new_answer: NewAnswer) -> Result<impl Reply, Rejection> {
     let account_id = session.account_id;
-    let content = match check_profanity(new_answer.content).await {
+    // deleted questions take no new answers
+    store.get_question(new_answer.question_id.0).await?;
+    let content = match check_profanity(profanity.as_ref(), new_answer.content).await {
         Ok(res) => res,
         Err(e) => return Err(warp::reject::custom(e))
     };
@@ -22,4 +26,57 @@ pub async fn add_answer(session: Session, store: Store, new_answer: NewAnswer) -
         Ok(_) => Ok(warp::reply::with_status("Answer added", StatusCode::OK)),
         Err(e) => Err(warp::reject::custom(e))
     }
-}
\ No newline at end of file
+}
+
+// answers of one question, oldest first; supports the same `limit`/`offset` parameters as `/questions`
+pub async fn get_answers(question_id: i32, params: HashMap<String, String>, store: DynStore) -> Result<impl Reply, Rejection> {
+    let mut pagination = pagination::Pagination::default();
+    if !params.is_empty() {
+        pagination = pagination::get_pagination(params)?;
+    }
+    // unknown questions are a 404, not an empty list
+    store.get_question(question_id).await?;
+    match store.get_answers(question_id, pagination.limit, pagination.offset).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn get_answer(id: i32, store: DynStore) -> Result<impl Reply, Rejection> {
+    match store.get_answer(id).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn update_answer(id: i32, session: Session, store: DynStore, profanity: DynProfanityChecker, answer: UpdateAnswer) -> Result<impl Reply, Rejection> {
+    let account_id = session.account_id;
+    // unknown answers are a 404 for everyone, not a 403 for all but moderators
+    store.get_answer(id).await?;
+    // only the author or a moderator may edit an answer
+    if session.role.can_moderate() || store.is_answer_owner(id, &account_id).await? {
+        let content = match check_profanity(profanity.as_ref(), answer.content).await {
+            Ok(res) => res,
+            Err(e) => return Err(warp::reject::custom(e))
+        };
+        match store.update_answer(content, id).await {
+            Ok(res) => Ok(warp::reply::json(&res)),
+            Err(e) => Err(warp::reject::custom(e))
+        }
+    } else {
+        Err(warp::reject::custom(handle_errors::WarpError::Unauthorized))
+    }
+}
+
+pub async fn delete_answer(id: i32, session: Session, store: DynStore) -> Result<impl Reply, Rejection> {
+    let account_id = session.account_id;
+    store.get_answer(id).await?;
+    if session.role.can_moderate() || store.is_answer_owner(id, &account_id).await? {
+        match store.delete_answer(id).await {
+            Ok(_) => Ok(warp::reply::with_status(format!("Answer {} deleted", id), StatusCode::OK)),
+            Err(e) => Err(warp::reject::custom(e))
+        }
+    } else {
+        Err(warp::reject::custom(handle_errors::WarpError::Unauthorized))
+    }
+}
@@ -3,85 +3,363 @@ use warp::{Reply, Rejection, Filter};
 use argon2::{self, Config};
 use rand::Rng;
 use chrono::Utc;
-use std::future;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use handle_errors::WarpError; // internal library
 
-use crate::store::Store;
-use crate::types::account::{Account, AccountId, Session};
+use crate::config::AuthConfig;
+use crate::rate_limit::RateLimiter;
+use crate::routes::email::AccountMailer;
+use crate::store::DynStore;
+use crate::types::account::{Account, AccountId, NewAccount, RefreshRequest, Role, Session, SessionId, TokenPair};
+use crate::validation::{self, PasswordPolicy};
 
 
 
-pub async fn register(store: Store, account: Account) -> Result<impl Reply, Rejection> {
+pub async fn register(store: DynStore, mails: AccountMailer, passwords: PasswordPolicy, account: NewAccount) -> Result<impl Reply, Rejection> {
+    let account = validation::validate_new_account(account, &passwords)?;
     let hashed_password = hash_password(account.password.as_bytes());
+    let email = account.email.clone();
     let account = Account {
-        id: account.id,
+        // assigned by the store
+        id: None,
         email: account.email,
-        password: hashed_password
+        password: hashed_password,
+        // new accounts always start out as unverified plain users
+        role: Role::User,
+        email_verified: false,
     };
     match store.add_account(account).await {
-        Ok(_) => Ok(warp::reply::with_status("Account added", StatusCode::OK)),
+        Ok(account_id) => {
+            mails.send_verification(&store, &account_id, &email).await?;
+            Ok(warp::reply::with_status("Account added", StatusCode::OK))
+        },
         Err(e) => Err(warp::reject::custom(e))
     }
 }
 
-pub async fn login(store: Store, login: Account) -> Result<impl Reply, Rejection> {
-    match store.get_account(login.email).await {
-        Ok(account) => match verify_password(&account.password, login.password.as_bytes()) {
-            Ok(verified) => { 
-                if verified {
-                    // issue user a token if login success
-                    Ok(warp::reply::json(&issue_token(account.id.expect("id not found!"))))
-                } else {
-                    Err(warp::reject::custom(handle_errors::WarpError::WrongPassword))
+pub async fn login(store: DynStore, tokens: TokenIssuer, limiter: RateLimiter, login: NewAccount) -> Result<impl Reply, Rejection> {
+    let email = login.email.clone();
+    limiter.check_login(&email).await?;
+    let account = match store.get_account(login.email).await {
+        Ok(account) => account,
+        // the same answer, in about the same time, as a wrong password, so logins can't be used to
+        // find out which emails are registered; the guesses count towards the lockout as well
+        Err(WarpError::NotFound) => {
+            let _ = verify_password(dummy_hash(), login.password.as_bytes());
+            limiter.login_failed(&email).await;
+            return Err(warp::reject::custom(WarpError::WrongPassword));
+        },
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    match verify_password(&account.password, login.password.as_bytes()) {
+        Ok(verified) => { 
+            if verified {
+                limiter.login_succeeded(&email).await;
+                // checked after the password, so the answer doesn't tell whether an email is registered
+                if tokens.require_verified_email && !account.email_verified {
+                    return Err(warp::reject::custom(handle_errors::WarpError::EmailNotVerified));
                 }
-            },
-            Err(e) => {
-                Err(warp::reject::custom(handle_errors::WarpError::ArgonLibraryError(e)))
+                // open a session and issue user a token pair if login success
+                let account_id = account.id.expect("id not found!");
+                let refresh_secret = generate_secret();
+                let session_id = store.create_session(&account_id, &hash_secret(&refresh_secret), tokens.refresh_token_ttl).await?;
+                Ok(warp::reply::json(&tokens.token_pair(account_id, account.role, session_id, &refresh_secret)))
+            } else {
+                // repeated wrong passwords lock the account for a growing amount of time
+                limiter.login_failed(&email).await;
+                Err(warp::reject::custom(handle_errors::WarpError::WrongPassword))
             }
         },
-        Err(e) => Err(warp::reject::custom(e)),
+        Err(e) => {
+            Err(warp::reject::custom(handle_errors::WarpError::ArgonLibraryError(e)))
+        }
+    }
+}
+
+// a hash to check passwords against when there is no account; hashed once, on the first use
+fn dummy_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| hash_password(&rand::thread_rng().gen::<[u8; 32]>()))
+}
+
+// exchanges a refresh token for a new access token and a new refresh token;
+// the presented refresh token stops working
+pub async fn refresh(store: DynStore, tokens: TokenIssuer, request: RefreshRequest) -> Result<impl Reply, Rejection> {
+    let (session_id, secret) = parse_refresh_token(&request.refresh_token)?;
+    let presented_hash = hash_secret(&secret);
+    let new_secret = generate_secret();
+
+    match store.rotate_refresh_token(&session_id, &presented_hash, &hash_secret(&new_secret)).await? {
+        Some((account_id, role)) => Ok(warp::reply::json(&tokens.token_pair(account_id, role, session_id, &new_secret))),
+        None => {
+            if store.revoke_session_on_reuse(&session_id, &presented_hash).await? {
+                tracing::event!(tracing::Level::WARN, session_id = session_id.0, "Refresh token reused, session revoked");
+            }
+            Err(warp::reject::custom(handle_errors::WarpError::InvalidRefreshToken))
+        }
+    }
+}
+
+// ends the session the access token belongs to
+pub async fn logout(session: Session, store: DynStore) -> Result<impl Reply, Rejection> {
+    match store.revoke_session(&session.session_id).await {
+        Ok(_) => Ok(warp::reply::with_status("Logged out", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+// ends every session of the account, e.g. after a device got lost
+pub async fn logout_all(session: Session, store: DynStore) -> Result<impl Reply, Rejection> {
+    match store.revoke_account_sessions(&session.account_id).await {
+        Ok(count) => Ok(warp::reply::with_status(format!("Logged out of {} sessions", count), StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e))
     }
 }
 
 /// helper functions below
-fn hash_password(password: &[u8]) -> String {
+pub fn hash_password(password: &[u8]) -> String {
     let salt = rand::thread_rng().gen::<[u8; 32]>();
     let config = Config::default(); // using the default configuration, we can also create our own
     argon2::hash_encoded(password, &salt, &config).unwrap()
 }
 
-fn verify_password(hash: &str, password: &[u8]) -> Result<bool, argon2::Error> {
+pub fn verify_password(hash: &str, password: &[u8]) -> Result<bool, argon2::Error> {
     argon2::verify_encoded(hash, password)
 }
 
-fn issue_token(account_id: AccountId) -> String {
+// the key ID travels unencrypted in the token footer, so verification knows which key to use
+#[derive(Serialize, Deserialize)]
+struct TokenFooter {
+    kid: String,
+}
+
+#[derive(Debug)]
+struct SigningKey {
+    key: Vec<u8>,
+    retired: bool,
+}
+
+/// All PASETO keys known to the server: new tokens are signed with the active key,
+/// tokens signed with any other non-retired key are still accepted
+#[derive(Clone, Debug)]
+struct KeyRing {
+    active: String,
+    keys: Arc<HashMap<String, SigningKey>>,
+}
+
+impl KeyRing {
+    // the config is validated at startup, so the active key is known to exist here
+    fn from_config(config: &AuthConfig) -> Self {
+        let keys = config.keys.iter()
+            .map(|key| (key.id.clone(), SigningKey {
+                key: key.key.as_bytes().to_vec(),
+                retired: key.retired,
+            }))
+            .collect();
+        Self {
+            active: config.active_key.clone(),
+            keys: Arc::new(keys),
+        }
+    }
+
+    fn active_key(&self) -> (&str, &[u8]) {
+        (&self.active, &self.keys[&self.active].key)
+    }
+
+    fn find(&self, kid: &str) -> Result<&[u8], handle_errors::WarpError> {
+        match self.keys.get(kid) {
+            Some(key) if key.retired => Err(handle_errors::WarpError::RetiredSigningKey),
+            Some(key) => Ok(&key.key),
+            None => Err(handle_errors::WarpError::CannotDecryptToken),
+        }
+    }
+
+}
+
+/// Hands out the token pairs returned by `/login` and `/refresh`
+#[derive(Clone, Debug)]
+pub struct TokenIssuer {
+    keys: KeyRing,
+    access_token_ttl: u64,
+    refresh_token_ttl: u64,
+    require_verified_email: bool,
+}
+
+impl TokenIssuer {
+    pub fn from_config(config: &AuthConfig) -> Self {
+        Self {
+            keys: KeyRing::from_config(config),
+            access_token_ttl: config.access_token_ttl,
+            refresh_token_ttl: config.refresh_token_ttl,
+            require_verified_email: config.require_verified_email,
+        }
+    }
+
+    fn token_pair(&self, account_id: AccountId, role: Role, session_id: SessionId, refresh_secret: &str) -> TokenPair {
+        TokenPair {
+            refresh_token: format!("{}.{}", session_id.0, refresh_secret),
+            access_token: issue_token(&self.keys, account_id, role, session_id, self.access_token_ttl),
+            expires_in: self.access_token_ttl,
+        }
+    }
+}
+
+fn issue_token(keys: &KeyRing, account_id: AccountId, role: Role, session_id: SessionId, ttl_secs: u64) -> String {
     let current_date_time = Utc::now();
-    let dt = current_date_time + chrono::Duration::days(1);
+    let dt = current_date_time + chrono::Duration::seconds(ttl_secs as i64);
+    let (kid, key) = keys.active_key();
+    let footer = serde_json::to_string(&TokenFooter { kid: kid.to_owned() }).expect("Failed to serialize token footer");
 
-    paseto::tokens::PasetoBuilder::new().set_encryption_key(&Vec::from("RANDOM WORDS WINTER MACINTOSH PC".as_bytes(),))
+    paseto::tokens::PasetoBuilder::new().set_encryption_key(key)
+        .set_footer(&footer)
         .set_expiration(&dt)
         .set_not_before(&Utc::now())
         .set_claim("account_id", serde_json::json!(account_id))
+        .set_claim("session_id", serde_json::json!(session_id))
+        .set_claim("role", serde_json::json!(role))
         .build()
         .expect("Failed to construct paseto token w/ builder!")
 }
 
-pub fn verify_token(token: String) -> Result<Session, handle_errors::WarpError> {
+// only checks the token itself; whether its session is still alive is up to `auth()`
+fn verify_token(keys: &KeyRing, token: String) -> Result<Session, handle_errors::WarpError> {
+    let footer = token_footer(&token)?;
+    let kid = serde_json::from_str::<TokenFooter>(&footer)
+        .map_err(|_| handle_errors::WarpError::CannotDecryptToken)?
+        .kid;
     let token = paseto::tokens::validate_local_token(
         &token,
-        None,
-        &"RANDOM WORDS WINTER MACINTOSH PC".as_bytes(),
+        Some(&footer),
+        keys.find(&kid)?,
         &paseto::tokens::TimeBackend::Chrono
     ).map_err(|_| handle_errors::WarpError::CannotDecryptToken)?;
 
     serde_json::from_value::<Session>(token).map_err(|_| { handle_errors::WarpError::CannotDecryptToken }) 
 }
 
-pub fn auth() -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
-    warp::header::<String>("Authorization").and_then(|token: String| {
-        let token = match verify_token(token) {
-            Ok(t) => t,
-            Err(_) => return future::ready(Err(warp::reject::reject())),
-        };
-        future::ready(Ok(token))
+// a local token looks like `v2.local.<payload>.<footer>`, both parts base64url encoded
+fn token_footer(token: &str) -> Result<String, handle_errors::WarpError> {
+    token.split('.').nth(3)
+        .and_then(|footer| base64::decode_config(footer, base64::URL_SAFE_NO_PAD).ok())
+        .and_then(|footer| String::from_utf8(footer).ok())
+        .ok_or(handle_errors::WarpError::CannotDecryptToken)
+}
+
+// refresh tokens are `<session id>.<random secret>`, tokens sent by email just the secret;
+// only a hash of the secret is stored
+pub fn generate_secret() -> String {
+    let secret = rand::thread_rng().gen::<[u8; 32]>();
+    base64::encode_config(secret, base64::URL_SAFE_NO_PAD)
+}
+
+pub fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+fn parse_refresh_token(token: &str) -> Result<(SessionId, String), handle_errors::WarpError> {
+    token.split_once('.')
+        .and_then(|(id, secret)| Some((SessionId(id.parse().ok()?), secret.to_owned())))
+        .ok_or(handle_errors::WarpError::InvalidRefreshToken)
+}
+
+pub fn auth(tokens: TokenIssuer, store: DynStore) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
+    warp::header::<String>("Authorization").and_then(move |token: String| {
+        let keys = tokens.keys.clone();
+        let store = store.clone();
+        async move {
+            let session = verify_token(&keys, token).map_err(warp::reject::custom)?;
+            // a valid signature is not enough, the session may have been logged out since
+            if store.is_session_active(&session.session_id).await? {
+                Ok(session)
+            } else {
+                Err(warp::reject::custom(handle_errors::WarpError::SessionRevoked))
+            }
+        }
     })
-}
\ No newline at end of file
+}
+
+// like `auth()`, but also rejects sessions whose role ranks below `role`
+pub fn require_role(role: Role, tokens: TokenIssuer, store: DynStore) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
+    auth(tokens, store).and_then(move |session: Session| async move {
+        if session.role >= role {
+            Ok(session)
+        } else {
+            Err(warp::reject::custom(handle_errors::WarpError::Unauthorized))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SigningKeyConfig;
+
+    fn key_ring(active: &str, keys: &[(&str, &str, bool)]) -> KeyRing {
+        KeyRing::from_config(&AuthConfig {
+            active_key: active.to_owned(),
+            keys: keys.iter()
+                .map(|(id, key, retired)| SigningKeyConfig { id: (*id).to_owned(), key: (*key).to_owned(), retired: *retired })
+                .collect(),
+            ..AuthConfig::default()
+        })
+    }
+
+    fn token(keys: &KeyRing) -> String {
+        issue_token(keys, AccountId(7), Role::User, SessionId(3), 60)
+    }
+
+    const OLD_KEY: &str = "00000000000000000000000000000000";
+    const NEW_KEY: &str = "11111111111111111111111111111111";
+
+    #[test]
+    fn tokens_carry_the_session_and_the_signing_key() {
+        let keys = key_ring("old", &[("old", OLD_KEY, false)]);
+        let token = token(&keys);
+
+        assert_eq!(token_footer(&token).unwrap(), r#"{"kid":"old"}"#);
+        let session = verify_token(&keys, token).unwrap();
+        assert_eq!(session.account_id, AccountId(7));
+        assert_eq!(session.session_id, SessionId(3));
+        assert_eq!(session.role, Role::User);
+    }
+
+    #[test]
+    fn tokens_of_the_previous_key_survive_a_rotation() {
+        let old_token = token(&key_ring("old", &[("old", OLD_KEY, false)]));
+        let rotated = key_ring("new", &[("old", OLD_KEY, false), ("new", NEW_KEY, false)]);
+
+        assert_eq!(rotated.active_key(), ("new", NEW_KEY.as_bytes()));
+        assert_eq!(token_footer(&token(&rotated)).unwrap(), r#"{"kid":"new"}"#);
+        assert!(verify_token(&rotated, old_token).is_ok());
+    }
+
+    #[test]
+    fn tokens_of_a_retired_key_are_rejected() {
+        let old_token = token(&key_ring("old", &[("old", OLD_KEY, false)]));
+        let retired = key_ring("new", &[("old", OLD_KEY, true), ("new", NEW_KEY, false)]);
+
+        assert!(matches!(retired.find("old"), Err(WarpError::RetiredSigningKey)));
+        assert!(matches!(verify_token(&retired, old_token), Err(WarpError::RetiredSigningKey)));
+    }
+
+    #[test]
+    fn tokens_of_an_unknown_key_are_rejected() {
+        let other_token = token(&key_ring("other", &[("other", OLD_KEY, false)]));
+        let keys = key_ring("new", &[("new", NEW_KEY, false)]);
+
+        assert!(matches!(keys.find("other"), Err(WarpError::CannotDecryptToken)));
+        assert!(matches!(verify_token(&keys, other_token), Err(WarpError::CannotDecryptToken)));
+    }
+
+    #[test]
+    fn tokens_signed_with_another_key_under_a_known_id_are_rejected() {
+        let forged = token(&key_ring("new", &[("new", OLD_KEY, false)]));
+        let keys = key_ring("new", &[("new", NEW_KEY, false)]);
+
+        assert!(matches!(verify_token(&keys, forged), Err(WarpError::CannotDecryptToken)));
+        assert!(matches!(verify_token(&keys, "forged".to_owned()), Err(WarpError::CannotDecryptToken)));
+    }
+}
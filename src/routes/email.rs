@@ -0,0 +1,121 @@
+// flows driven by single-use tokens sent by email: verifying the address and resetting a forgotten password
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+use handle_errors::WarpError; // internal library
+
+use crate::config::MailConfig;
+use crate::mail::{self, DynMailer, Mail, MailError};
+use crate::routes::authentication::{generate_secret, hash_password, hash_secret};
+use crate::store::DynStore;
+use crate::types::account::{Account, AccountId, EmailRequest, PasswordReset, TokenPurpose, VerifyEmail};
+use crate::validation::PasswordPolicy;
+
+// the same answer whether or not the account exists, so these routes can't be used to probe for emails
+const RESEND_REPLY: &str = "If the account exists and is not verified yet, a new verification email is on its way";
+const FORGOT_REPLY: &str = "If the account exists, a password reset email is on its way";
+
+/// Issues the tokens of the email flows and sends them out
+#[derive(Clone, Debug)]
+pub struct AccountMailer {
+    mailer: DynMailer,
+    link_base_url: String,
+    verification_token_ttl: u64,
+    reset_token_ttl: u64,
+}
+
+impl AccountMailer {
+    pub fn from_config(config: &MailConfig) -> Result<Self, MailError> {
+        Ok(Self {
+            mailer: mail::from_config(config)?,
+            link_base_url: config.link_base_url.trim_end_matches('/').to_owned(),
+            verification_token_ttl: config.verification_token_ttl,
+            reset_token_ttl: config.reset_token_ttl,
+        })
+    }
+
+    pub async fn send_verification(&self, store: &DynStore, account_id: &AccountId, email: &str) -> Result<(), WarpError> {
+        let token = generate_secret();
+        store.create_account_token(account_id, TokenPurpose::VerifyEmail, &hash_secret(&token), self.verification_token_ttl).await?;
+        mail::send_in_background(self.mailer.clone(), Mail {
+            to: email.to_owned(),
+            subject: "Please verify your email address".to_owned(),
+            body: format!(
+                "Welcome!\n\nPlease confirm your email address by opening this link:\n{}/verify-email?token={}\n\nThe link expires in {}. If you did not sign up, you can ignore this email.\n",
+                self.link_base_url, token, expires_in(self.verification_token_ttl)
+            ),
+        });
+        Ok(())
+    }
+
+    async fn send_password_reset(&self, store: &DynStore, account_id: &AccountId, email: &str) -> Result<(), WarpError> {
+        let token = generate_secret();
+        store.create_account_token(account_id, TokenPurpose::ResetPassword, &hash_secret(&token), self.reset_token_ttl).await?;
+        mail::send_in_background(self.mailer.clone(), Mail {
+            to: email.to_owned(),
+            subject: "Reset your password".to_owned(),
+            body: format!(
+                "Someone asked to reset the password of your account.\n\nChoose a new password here:\n{}/reset-password?token={}\n\nThe link expires in {}. If this wasn't you, you can ignore this email; your password stays the same.\n",
+                self.link_base_url, token, expires_in(self.reset_token_ttl)
+            ),
+        });
+        Ok(())
+    }
+}
+
+pub async fn verify_email(store: DynStore, request: VerifyEmail) -> Result<impl Reply, Rejection> {
+    match store.use_account_token(TokenPurpose::VerifyEmail, &hash_secret(&request.token)).await? {
+        Some(account_id) => {
+            store.set_email_verified(&account_id).await?;
+            Ok(warp::reply::with_status("Email verified", StatusCode::OK))
+        },
+        None => Err(warp::reject::custom(WarpError::InvalidEmailToken)),
+    }
+}
+
+pub async fn resend_verification(store: DynStore, mails: AccountMailer, request: EmailRequest) -> Result<impl Reply, Rejection> {
+    if let Some(account) = find_account(&store, request.email).await? {
+        if !account.email_verified {
+            if let Some(account_id) = &account.id {
+                mails.send_verification(&store, account_id, &account.email).await?;
+            }
+        }
+    }
+    Ok(warp::reply::with_status(RESEND_REPLY, StatusCode::OK))
+}
+
+pub async fn forgot_password(store: DynStore, mails: AccountMailer, request: EmailRequest) -> Result<impl Reply, Rejection> {
+    if let Some(Account { id: Some(account_id), email, .. }) = find_account(&store, request.email).await? {
+        mails.send_password_reset(&store, &account_id, &email).await?;
+    }
+    Ok(warp::reply::with_status(FORGOT_REPLY, StatusCode::OK))
+}
+
+// the new password ends every login session; the email arrived, so the address counts as verified
+pub async fn reset_password(store: DynStore, passwords: PasswordPolicy, request: PasswordReset) -> Result<impl Reply, Rejection> {
+    // checked before the token is used up, so a rejected password can be fixed with the same link;
+    // the account and its email aren't known yet at this point
+    passwords.validate("password", &request.password, None)?;
+    let account_id = match store.use_account_token(TokenPurpose::ResetPassword, &hash_secret(&request.token)).await? {
+        Some(account_id) => account_id,
+        None => return Err(warp::reject::custom(WarpError::InvalidEmailToken)),
+    };
+    store.set_password(&account_id, &hash_password(request.password.as_bytes())).await?;
+    store.set_email_verified(&account_id).await?;
+    store.revoke_account_sessions(&account_id).await?;
+    Ok(warp::reply::with_status("Password changed", StatusCode::OK))
+}
+
+async fn find_account(store: &DynStore, email: String) -> Result<Option<Account>, WarpError> {
+    match store.get_account(email).await {
+        Ok(account) => Ok(Some(account)),
+        Err(WarpError::NotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn expires_in(secs: u64) -> String {
+    match secs {
+        0..=5399 => format!("{} minutes", (secs / 60).max(1)),
+        _ => format!("{} hours", (secs + 1800) / 3600),
+    }
+}
@@ -1,3 +1,1210 @@
+use std::convert::Infallible;
+use warp::{http::Method, Filter, Reply};
+
+use crate::health::{self, HealthChecker};
+use crate::metrics::{self, Metrics};
+use crate::profanity::DynProfanityChecker;
+use crate::rate_limit::{self, RateLimiter};
+use crate::store::DynStore;
+use crate::telemetry;
+use crate::types::account::Role;
+use crate::types::revision::RollbackRequest;
+use crate::validation::PasswordPolicy;
+
 pub mod question;
 pub mod answer;
-pub mod authentication;
\ No newline at end of file
+pub mod authentication;
+pub mod admin;
+pub mod search;
+pub mod vote;
+pub mod email;
+pub mod account;
+
+/// The complete filter tree of the API, independent of the storage backend
+#[allow(clippy::too_many_arguments)]
+pub fn router(store: DynStore, tokens: authentication::TokenIssuer, profanity: DynProfanityChecker, limiter: RateLimiter, mails: email::AccountMailer, passwords: PasswordPolicy, metrics: Metrics, health: HealthChecker) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    // verifies the access token and checks that its session was not revoked
+    let auth_filter = authentication::auth(tokens.clone(), store.clone());
+    // same, and additionally requires an admin account
+    let admin_filter = authentication::require_role(Role::Admin, tokens.clone(), store.clone());
+
+    let store_filter = warp::any().map(move || store.clone());
+    let tokens_filter = warp::any().map(move || tokens.clone());
+    let profanity_filter = warp::any().map(move || profanity.clone());
+    let mails_filter = warp::any().map(move || mails.clone());
+    let passwords_filter = warp::any().map(move || passwords.clone());
+    let health_filter = warp::any().map(move || health.clone());
+    let metrics_filter = warp::any().map({
+        let metrics = metrics.clone();
+        move || metrics.clone()
+    });
+    // the rate limits only apply once the path matched, so other routes don't use up the buckets
+    let limiter_filter = warp::any().map({
+        let limiter = limiter.clone();
+        move || limiter.clone()
+    });
+    // new questions and answers are limited per client address and per account
+    let write_filter = rate_limit::per_ip(limiter.clone(), "write")
+        .and(rate_limit::per_account(auth_filter.clone(), limiter.clone(), "write"));
+    // the self-service routes check the password, so guessing it with a stolen token is slow
+    let account_filter = rate_limit::per_account(auth_filter.clone(), limiter.clone(), "account");
+
+    // Cross Origin
+    let cors = warp::cors()
+        .allow_any_origin()
+        .allow_headers(["content-type", "x-request-id"])
+        .expose_header("x-request-id")
+        .allow_methods(&[Method::PUT, Method::DELETE, Method::GET, Method::POST]);
+
+    let get_questions = warp::get()
+        .and(warp::path("questions"))
+        .and(warp::path::end())
+        // `warp::query::raw()` rejects requests without a query string
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+        .and(store_filter.clone())
+        .and_then(question::get_question);
+
+    let get_single_question = warp::get()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and_then(question::get_single_question);
+
+    let get_answers = warp::get()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("answers"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(answer::get_answers);
+
+    let get_answer = warp::get()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and_then(answer::get_answer);
+
+    let search = warp::get()
+        .and(warp::path("search"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(search::search);
+
+    let login = warp::post()
+        .and(warp::path("login"))
+        .and(warp::path::end())
+        .and(rate_limit::per_ip(limiter.clone(), "login"))
+        .and(store_filter.clone())
+        .and(tokens_filter.clone())
+        .and(limiter_filter.clone())
+        .and(warp::body::json())
+        .and_then(authentication::login);
+
+    let add_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::end())
+        .and(write_filter.clone())
+        .and(store_filter.clone())
+        .and(profanity_filter.clone())
+        .and(warp::body::json())
+        .and_then(question::add_question);
+
+    let update_question = warp::put()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end()) // closes the path definition
+        .and(auth_filter.clone()) // verify the token only when the client is attempting to manipulate data
+        .and(store_filter.clone()) // adds our store to the route so we can pass it to the route handler later
+        .and(profanity_filter.clone())
+        .and(warp::body::json()) // extracts the JSON body that's added to the parameters as well
+        .and_then(question::update_question);
+
+    let delete_question = warp::delete()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        .and_then(question::delete_question);
+
+    let restore_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("restore"))
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        .and_then(question::restore_question);
+
+    let add_answer = warp::post()
+        .and(warp::path("answers"))
+        .and(warp::path::end())
+        .and(write_filter.clone())
+        .and(store_filter.clone())
+        .and(profanity_filter.clone())
+        .and(warp::body::form()) // this uses *url-form encoded, instead of JSON
+        .and_then(answer::add_answer);
+
+    let update_answer = warp::put()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        .and(profanity_filter.clone())
+        .and(warp::body::json())
+        .and_then(answer::update_answer);
+
+    let delete_answer = warp::delete()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        .and_then(answer::delete_answer);
+
+    let accept_answer = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("accept"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        .and_then(question::accept_answer);
+
+    let unaccept_answer = warp::delete()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("accept"))
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        .and_then(question::unaccept_answer);
+
+    let get_revisions = warp::get()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and_then(question::get_revisions);
+
+    let get_revision_diff = warp::get()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("diff"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and_then(question::get_revision_diff);
+
+    let rollback_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("rollback"))
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        // the body with a reason is optional
+        .and(warp::body::json().or(warp::any().map(RollbackRequest::default)).unify())
+        .and_then(question::rollback_question);
+
+    let vote_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(vote::vote_question);
+
+    let unvote_question = warp::delete()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        .and_then(vote::unvote_question);
+
+    let vote_answer = warp::post()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(vote::vote_answer);
+
+    let unvote_answer = warp::delete()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        .and_then(vote::unvote_answer);
+
+    let refresh = warp::post()
+        .and(warp::path("refresh"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(tokens_filter.clone())
+        .and(warp::body::json())
+        .and_then(authentication::refresh);
+
+    let logout = warp::post()
+        .and(warp::path("logout"))
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        .and_then(authentication::logout);
+
+    let logout_all = warp::post()
+        .and(warp::path("logout-all"))
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(store_filter.clone())
+        .and_then(authentication::logout_all);
+
+    let registration = warp::post()
+        .and(warp::path("registration"))
+        .and(warp::path::end())
+        .and(rate_limit::per_ip(limiter.clone(), "registration"))
+        .and(store_filter.clone())
+        .and(mails_filter.clone())
+        .and(passwords_filter.clone())
+        .and(warp::body::json())
+        .and_then(authentication::register);
+
+    let verify_email = warp::post()
+        .and(warp::path("email"))
+        .and(warp::path("verify"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(email::verify_email);
+
+    // both send an email, so they share a bucket per client address
+    let resend_verification = warp::post()
+        .and(warp::path("email"))
+        .and(warp::path("resend"))
+        .and(warp::path::end())
+        .and(rate_limit::per_ip(limiter.clone(), "email"))
+        .and(store_filter.clone())
+        .and(mails_filter.clone())
+        .and(warp::body::json())
+        .and_then(email::resend_verification);
+
+    let forgot_password = warp::post()
+        .and(warp::path("password"))
+        .and(warp::path("forgot"))
+        .and(warp::path::end())
+        .and(rate_limit::per_ip(limiter.clone(), "email"))
+        .and(store_filter.clone())
+        .and(mails_filter.clone())
+        .and(warp::body::json())
+        .and_then(email::forgot_password);
+
+    let reset_password = warp::post()
+        .and(warp::path("password"))
+        .and(warp::path("reset"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(passwords_filter.clone())
+        .and(warp::body::json())
+        .and_then(email::reset_password);
+
+    let change_password = warp::put()
+        .and(warp::path("account"))
+        .and(warp::path("password"))
+        .and(warp::path::end())
+        .and(account_filter.clone())
+        .and(store_filter.clone())
+        .and(passwords_filter.clone())
+        .and(warp::body::json())
+        .and_then(account::change_password);
+
+    let change_email = warp::put()
+        .and(warp::path("account"))
+        .and(warp::path("email"))
+        .and(warp::path::end())
+        .and(account_filter.clone())
+        .and(store_filter.clone())
+        .and(mails_filter.clone())
+        .and(warp::body::json())
+        .and_then(account::change_email);
+
+    let delete_own_account = warp::delete()
+        .and(warp::path("account"))
+        .and(warp::path::end())
+        .and(account_filter.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(account::delete_account);
+
+    let export_account = warp::get()
+        .and(warp::path("account"))
+        .and(warp::path("export"))
+        .and(warp::path::end())
+        .and(account_filter.clone())
+        .and(store_filter.clone())
+        .and_then(account::export_account);
+
+    let get_metrics = warp::get()
+        .and(warp::path("metrics"))
+        .and(warp::path::end())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(metrics_filter)
+        .and_then(metrics::serve);
+
+    // no rate limit and no authentication, the orchestrator polls these
+    let get_liveness = warp::get()
+        .and(warp::path("health"))
+        .and(warp::path("live"))
+        .and(warp::path::end())
+        .and_then(health::live);
+
+    let get_readiness = warp::get()
+        .and(warp::path("health"))
+        .and(warp::path("ready"))
+        .and(warp::path::end())
+        .and(health_filter)
+        .and_then(health::ready);
+
+    let get_accounts = warp::get()
+        .and(warp::path("admin"))
+        .and(warp::path("accounts"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(admin_filter.clone())
+        .and(store_filter.clone())
+        .and_then(admin::get_accounts);
+
+    let update_account_role = warp::put()
+        .and(warp::path("admin"))
+        .and(warp::path("accounts"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("role"))
+        .and(warp::path::end())
+        .and(admin_filter.clone())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(admin::update_role);
+
+    let delete_account = warp::delete()
+        .and(warp::path("admin"))
+        .and(warp::path("accounts"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(admin_filter.clone())
+        .and(store_filter.clone())
+        .and_then(admin::delete_account);
+
+    // boxed in two groups, so the type of the combined filter stays within the compiler's limits
+    let question_routes = get_questions
+        .or(get_single_question)
+        .or(get_answers)
+        .or(get_answer)
+        .or(search)
+        .or(add_question)
+        .or(update_question)
+        .or(add_answer)
+        .or(update_answer)
+        .or(delete_answer)
+        .or(delete_question)
+        .or(restore_question)
+        .or(accept_answer)
+        .or(unaccept_answer)
+        .or(get_revisions)
+        .or(get_revision_diff)
+        .or(rollback_question)
+        .or(vote_question)
+        .or(unvote_question)
+        .or(vote_answer)
+        .or(unvote_answer)
+        .boxed();
+
+    let account_routes = registration
+        .or(verify_email)
+        .or(resend_verification)
+        .or(forgot_password)
+        .or(reset_password)
+        .or(login)
+        .or(refresh)
+        .or(logout)
+        .or(logout_all)
+        .or(change_password)
+        .or(change_email)
+        .or(delete_own_account)
+        .or(export_account)
+        .or(get_accounts)
+        .or(update_account_role)
+        .or(delete_account)
+        .or(get_metrics)
+        .or(get_liveness)
+        .or(get_readiness)
+        .boxed();
+
+    // every response, including the errors, carries the request ID
+    telemetry::with_request_id(question_routes.or(account_routes).with(cors))
+        .with(telemetry::record_status())
+        .with(telemetry::request_span()) // one span per request, the parent of everything it does
+        .with(metrics::log(metrics))
+
+}
+
+// the whole filter tree on the in-memory store, the way a client sees it
+#[cfg(all(test, feature = "in-memory"))]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use serde_json::{json, Value};
+    use warp::http::StatusCode;
+
+    use super::*;
+    use crate::config::{AuthConfig, HealthConfig, MailConfig, MailTransport, PasswordConfig, RateLimitConfig, SigningKeyConfig};
+    use crate::profanity::WordListChecker;
+    use crate::store::InMemoryStore;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    struct Api<F> {
+        routes: F,
+        store: DynStore,
+        // emails are written here as `.eml` files; `seen` are the ones a test already read
+        mail_dir: PathBuf,
+        seen: Mutex<Vec<PathBuf>>,
+    }
+
+    impl<F> Drop for Api<F> {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.mail_dir);
+        }
+    }
+
+    fn api() -> Api<impl Filter<Extract = impl Reply, Error = Infallible> + Clone + 'static> {
+        api_with(|_, _| ())
+    }
+
+    // `configure` adjusts the auth and mail settings before the router is built
+    fn api_with(configure: impl FnOnce(&mut AuthConfig, &mut MailConfig)) -> Api<impl Filter<Extract = impl Reply, Error = Infallible> + Clone + 'static> {
+        let store: DynStore = Arc::new(InMemoryStore::new());
+        let mut auth = AuthConfig {
+            active_key: "test".to_owned(),
+            keys: vec![SigningKeyConfig { id: "test".to_owned(), key: "0123456789abcdef0123456789abcdef".to_owned(), retired: false }],
+            require_verified_email: false,
+            ..AuthConfig::default()
+        };
+        let mail_dir = std::env::temp_dir().join(format!("webapp_api-mail-{}", uuid::Uuid::new_v4()));
+        let mut mail = MailConfig { transport: Some(MailTransport::File), file_dir: mail_dir.clone(), ..MailConfig::default() };
+        configure(&mut auth, &mut mail);
+        let tokens = authentication::TokenIssuer::from_config(&auth);
+        let profanity: DynProfanityChecker = Arc::new(WordListChecker::from_list("shit"));
+        let limiter = RateLimiter::from_config(&RateLimitConfig::default());
+        let mails = email::AccountMailer::from_config(&mail).unwrap();
+        let passwords = PasswordPolicy::from_config(&PasswordConfig::default()).unwrap();
+        let metrics = Metrics::new();
+        let health = HealthChecker::new(store.clone(), profanity.clone(), &HealthConfig::default());
+        let routes = router(store.clone(), tokens, profanity, limiter, mails, passwords, metrics, health);
+        Api { routes, store, mail_dir, seen: Mutex::new(Vec::new()) }
+    }
+
+    impl<F> Api<F>
+    where
+        F: Filter + Clone + 'static,
+        F::Extract: Reply + Send,
+    {
+        async fn call(&self, request: warp::test::RequestBuilder) -> (StatusCode, Value) {
+            let res = request.reply(&self.routes).await;
+            let body = serde_json::from_slice(res.body()).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(res.body()).into_owned()));
+            (res.status(), body)
+        }
+
+        // registers the account and logs it in with `role`, returning the access token
+        async fn login_as(&self, email: &str, role: Role) -> String {
+            let account = json!({ "email": email, "password": PASSWORD });
+            let (status, _) = self.call(warp::test::request().method("POST").path("/registration").json(&account)).await;
+            assert_eq!(status, StatusCode::OK);
+            if role != Role::User {
+                let account_id = self.store.get_account(email.to_owned()).await.unwrap().id.unwrap();
+                self.store.set_account_role(&account_id, role).await.unwrap();
+            }
+            self.login(email).await["access_token"].as_str().unwrap().to_owned()
+        }
+
+        // the token pair of a new session of a registered account
+        async fn login(&self, email: &str) -> Value {
+            let account = json!({ "email": email, "password": PASSWORD });
+            let (status, body) = self.call(warp::test::request().method("POST").path("/login").json(&account)).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            body
+        }
+
+        // the token of the next unread email with `subject`; emails are sent in the background
+        async fn mailed_token(&self, subject: &str) -> String {
+            for _ in 0..100 {
+                for entry in std::fs::read_dir(&self.mail_dir).into_iter().flatten() {
+                    let path = entry.unwrap().path();
+                    let mut seen = self.seen.lock().unwrap();
+                    let message = std::fs::read_to_string(&path).unwrap_or_default();
+                    if seen.contains(&path) || !message.contains(&format!("Subject: {}", subject)) {
+                        continue;
+                    }
+                    seen.push(path);
+                    // the body is quoted-printable: undo the soft line breaks and the escaped `=`
+                    let body = message.replace("=\r\n", "").replace("=\n", "").replace("=3D", "=");
+                    let token = body.split("token=").nth(1).expect("email without a token");
+                    return token.split_whitespace().next().unwrap().to_owned();
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            panic!("no email with subject `{}`", subject);
+        }
+
+        // whether an access token still gets through `auth()`, and the problem code if not
+        async fn session_check(&self, token: &str) -> (StatusCode, Value) {
+            let (status, body) = self.call(warp::test::request().path("/account/export").header("Authorization", token)).await;
+            (status, body["code"].clone())
+        }
+
+        async fn refresh(&self, refresh_token: &Value) -> (StatusCode, Value) {
+            self.call(warp::test::request().method("POST").path("/refresh").json(&json!({ "refresh_token": refresh_token }))).await
+        }
+
+        // titles of the listed questions, e.g. for `answered=true`
+        async fn question_titles(&self, query: &str) -> Vec<Value> {
+            let (status, questions) = self.call(warp::test::request().path(&format!("/questions?{}", query))).await;
+            assert_eq!(status, StatusCode::OK, "{}", questions);
+            questions.as_array().unwrap().iter().map(|question| question["title"].clone()).collect()
+        }
+
+        async fn account_id(&self, email: &str) -> i32 {
+            self.store.get_account(email.to_owned()).await.unwrap().id.unwrap().0
+        }
+
+        async fn add_question(&self, token: &str, title: &str) -> i64 {
+            let question = json!({ "title": title, "content": "What does the borrow checker want?", "tags": ["rust"] });
+            let (status, body) = self.call(warp::test::request().method("POST").path("/questions").header("Authorization", token).json(&question)).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            body["id"].as_i64().unwrap()
+        }
+
+        async fn add_answer(&self, token: &str, question_id: i64) -> i64 {
+            let (status, body) = self.call(warp::test::request()
+                .method("POST")
+                .path("/answers")
+                .header("Authorization", token)
+                .header("content-type", "application/x-www-form-urlencoded")
+                .body(format!("question_id={}&content=Clone+it", question_id))).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            let (_, answers) = self.call(warp::test::request().path(&format!("/questions/{}/answers", question_id))).await;
+            answers.as_array().unwrap().last().unwrap()["id"].as_i64().unwrap()
+        }
+    }
+
+    fn put(path: &str, token: &str, body: &Value) -> warp::test::RequestBuilder {
+        warp::test::request().method("PUT").path(path).header("Authorization", token).json(body)
+    }
+
+    fn delete(path: &str, token: &str) -> warp::test::RequestBuilder {
+        warp::test::request().method("DELETE").path(path).header("Authorization", token)
+    }
+
+    #[tokio::test]
+    async fn questions_and_answers_round_trip() {
+        let api = api();
+        let token = api.login_as("jane@example.com", Role::User).await;
+        let id = api.add_question(&token, "Why shit?").await;
+
+        let (status, question) = api.call(warp::test::request().path(&format!("/questions/{}", id))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(question["title"], "Why ****?");
+        assert_eq!(question["answers"], json!([]));
+
+        let update = json!({ "title": "Why borrow?", "content": "Edited", "tags": ["rust"] });
+        let (status, question) = api.call(put(&format!("/questions/{}", id), &token, &update)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(question["content"], "Edited");
+
+        let answer_id = api.add_answer(&token, id).await;
+        let (status, answer) = api.call(warp::test::request().path(&format!("/answers/{}", answer_id))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(answer["content"], "Clone it");
+
+        let (_, questions) = api.call(warp::test::request().path("/questions")).await;
+        assert_eq!(questions.as_array().unwrap().len(), 1);
+
+        let (status, _) = api.call(delete(&format!("/questions/{}", id), &token)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, problem) = api.call(warp::test::request().path(&format!("/questions/{}", id))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(problem["code"], "not_found");
+    }
+
+    #[tokio::test]
+    async fn answers_can_only_be_changed_by_their_author() {
+        let api = api();
+        let author = api.login_as("jane@example.com", Role::User).await;
+        let other = api.login_as("john@example.com", Role::User).await;
+        let question_id = api.add_question(&author, "Lifetimes").await;
+        let answer_id = api.add_answer(&author, question_id).await;
+        let path = format!("/answers/{}", answer_id);
+
+        let (status, problem) = api.call(put(&path, &other, &json!({ "content": "Hijacked" }))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(problem["code"], "forbidden");
+        let (status, _) = api.call(delete(&path, &other)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, answer) = api.call(put(&path, &author, &json!({ "content": "Borrow it" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(answer["content"], "Borrow it");
+        let (status, _) = api.call(delete(&path, &author)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = api.call(warp::test::request().path(&path)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn unknown_answers_are_not_found() {
+        let api = api();
+        let user = api.login_as("jane@example.com", Role::User).await;
+        let (status, _) = api.call(warp::test::request().path("/answers/999")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = api.call(put("/answers/999", &user, &json!({ "content": "Edited" }))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = api.call(delete("/answers/999", &user)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn moderators_may_change_any_content() {
+        let api = api();
+        let author = api.login_as("jane@example.com", Role::User).await;
+        let other = api.login_as("john@example.com", Role::User).await;
+        let moderator = api.login_as("mod@example.com", Role::Moderator).await;
+        let question_id = api.add_question(&author, "Lifetimes").await;
+        let answer_id = api.add_answer(&author, question_id).await;
+        let question = format!("/questions/{}", question_id);
+        let answer = format!("/answers/{}", answer_id);
+        let update = json!({ "title": "Moderated", "content": "Moderated", "tags": null });
+
+        let (status, problem) = api.call(put(&question, &other, &update)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(problem["code"], "forbidden");
+        let (status, _) = api.call(delete(&question, &other)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = api.call(put(&answer, &moderator, &json!({ "content": "Moderated" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["content"], "Moderated");
+        let (status, body) = api.call(put(&question, &moderator, &update)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["title"], "Moderated");
+        let (status, _) = api.call(delete(&answer, &moderator)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = api.call(delete(&question, &moderator)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn only_admins_manage_roles() {
+        let api = api();
+        let user = api.login_as("jane@example.com", Role::User).await;
+        let moderator = api.login_as("mod@example.com", Role::Moderator).await;
+        let admin = api.login_as("admin@example.com", Role::Admin).await;
+        let (status, _) = api.call(warp::test::request().path("/admin/accounts").header("Authorization", &moderator)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, accounts) = api.call(warp::test::request().path("/admin/accounts").header("Authorization", &admin)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(accounts.as_array().unwrap().len(), 3);
+
+        let path = format!("/admin/accounts/{}/role", api.account_id("jane@example.com").await);
+        let (status, account) = api.call(put(&path, &admin, &json!({ "role": "moderator" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(account["role"], "moderator");
+        // the old token still carries the old role, so its session is gone
+        let (status, problem) = api.call(put("/answers/1", &user, &json!({ "content": "Edited" }))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(problem["code"], "session_revoked");
+    }
+
+    #[tokio::test]
+    async fn deleted_questions_can_be_restored_by_their_author() {
+        let api = api();
+        let author = api.login_as("jane@example.com", Role::User).await;
+        let other = api.login_as("john@example.com", Role::User).await;
+        let id = api.add_question(&author, "Lifetimes").await;
+        let path = format!("/questions/{}", id);
+        let restore = |token: &str| warp::test::request().method("POST").path(&format!("{}/restore", path)).header("Authorization", token);
+
+        let (status, _) = api.call(delete(&path, &author)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, questions) = api.call(warp::test::request().path("/questions")).await;
+        assert_eq!(questions, json!([]));
+        let (status, _) = api.call(restore(&other)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) = api.call(restore(&author)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = api.call(warp::test::request().path(&path)).await;
+        assert_eq!(status, StatusCode::OK);
+        // only deleted questions can be restored
+        let (status, _) = api.call(restore(&author)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn unknown_questions_are_not_found() {
+        let api = api();
+        let author = api.login_as("jane@example.com", Role::User).await;
+        let user = api.login_as("john@example.com", Role::User).await;
+        let moderator = api.login_as("mod@example.com", Role::Moderator).await;
+        let live = api.add_question(&author, "Lifetimes").await;
+        let update = json!({ "title": "Title", "content": "Content", "tags": null });
+        let restore = |id: i64, token: &str| warp::test::request().method("POST").path(&format!("/questions/{}/restore", id)).header("Authorization", token);
+
+        let (status, _) = api.call(warp::test::request().path("/questions/999")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        // a 404 rather than a 403 for everyone, so plain users can't tell which IDs exist
+        for token in [&user, &moderator] {
+            let (status, _) = api.call(put("/questions/999", token, &update)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            let (status, _) = api.call(delete("/questions/999", token)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            let (status, _) = api.call(restore(999, token)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            // not deleted, so there is nothing to restore
+            let (status, _) = api.call(restore(live, token)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_of_deleted_questions_are_gone() {
+        let api = api();
+        let author = api.login_as("jane@example.com", Role::User).await;
+        let moderator = api.login_as("mod@example.com", Role::Moderator).await;
+        let question_id = api.add_question(&author, "Lifetimes").await;
+        let answer_id = api.add_answer(&author, question_id).await;
+
+        let (status, _) = api.call(delete(&format!("/questions/{}", question_id), &author)).await;
+        assert_eq!(status, StatusCode::OK);
+        for token in [&author, &moderator] {
+            let (status, _) = api.call(put(&format!("/answers/{}", answer_id), token, &json!({ "content": "Edited" }))).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            let (status, _) = api.call(delete(&format!("/answers/{}", answer_id), token)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_need_a_valid_token() {
+        let api = api();
+        let question = json!({ "title": "Forged", "content": "Who signed this?" });
+        let (status, problem) = api.call(warp::test::request().method("POST").path("/questions")
+            .header("Authorization", "forged").json(&question)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(problem["code"], "invalid_token");
+    }
+
+    #[tokio::test]
+    async fn unknown_email_fails_like_a_wrong_password() {
+        let api = api();
+        api.login_as("jane@example.com", Role::User).await;
+        for email in ["jane@example.com", "nobody@example.com"] {
+            let login = json!({ "email": email, "password": "wrong password" });
+            let (status, problem) = api.call(warp::test::request().method("POST").path("/login").json(&login)).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(problem["code"], "invalid_credentials");
+        }
+    }
+
+    #[tokio::test]
+    async fn repeated_wrong_passwords_lock_the_account() {
+        let api = api();
+        api.login_as("jane@example.com", Role::User).await;
+        let wrong = json!({ "email": "jane@example.com", "password": "wrong password" });
+        for _ in 0..RateLimitConfig::default().lockout.max_failures {
+            let (status, _) = api.call(warp::test::request().method("POST").path("/login").json(&wrong)).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+        // even the right password is refused until the lock ends
+        let right = json!({ "email": "jane@example.com", "password": PASSWORD });
+        let res = warp::test::request().method("POST").path("/login").json(&right).reply(&api.routes).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()["retry-after"], "30");
+    }
+
+    #[tokio::test]
+    async fn logins_wait_for_the_verified_email() {
+        let api = api_with(|auth, _| auth.require_verified_email = true);
+        let account = json!({ "email": "jane@example.com", "password": PASSWORD });
+        let (status, _) = api.call(warp::test::request().method("POST").path("/registration").json(&account)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, problem) = api.call(warp::test::request().method("POST").path("/login").json(&account)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(problem["code"], "email_not_verified");
+
+        // a resent email gets a new token; using either one uses up both
+        let first = api.mailed_token("Please verify your email address").await;
+        let resend = json!({ "email": "jane@example.com" });
+        let (status, _) = api.call(warp::test::request().method("POST").path("/email/resend").json(&resend)).await;
+        assert_eq!(status, StatusCode::OK);
+        let token = api.mailed_token("Please verify your email address").await;
+        assert_ne!(first, token);
+
+        let (status, _) = api.call(warp::test::request().method("POST").path("/email/verify").json(&json!({ "token": token }))).await;
+        assert_eq!(status, StatusCode::OK);
+        api.login("jane@example.com").await;
+        for token in [first, token] {
+            let (status, problem) = api.call(warp::test::request().method("POST").path("/email/verify").json(&json!({ "token": token }))).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(problem["code"], "invalid_email_token");
+        }
+    }
+
+    #[tokio::test]
+    async fn password_reset_tokens_work_once() {
+        let api = api();
+        let old_token = api.login_as("jane@example.com", Role::User).await;
+        for email in ["jane@example.com", "nobody@example.com"] {
+            let (status, reply) = api.call(warp::test::request().method("POST").path("/password/forgot").json(&json!({ "email": email }))).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(reply, "If the account exists, a password reset email is on its way");
+        }
+        let token = api.mailed_token("Reset your password").await;
+
+        let reset = json!({ "token": token, "password": "a brand new passphrase" });
+        let (status, _) = api.call(warp::test::request().method("POST").path("/password/reset").json(&reset)).await;
+        assert_eq!(status, StatusCode::OK);
+        let login = json!({ "email": "jane@example.com", "password": "a brand new passphrase" });
+        let (status, _) = api.call(warp::test::request().method("POST").path("/login").json(&login)).await;
+        assert_eq!(status, StatusCode::OK);
+        // the reset ends every session
+        let (status, problem) = api.call(warp::test::request().method("POST").path("/logout").header("Authorization", &old_token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(problem["code"], "session_revoked");
+
+        let again = json!({ "token": token, "password": "yet another passphrase" });
+        let (status, problem) = api.call(warp::test::request().method("POST").path("/password/reset").json(&again)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(problem["code"], "invalid_email_token");
+    }
+
+    #[tokio::test]
+    async fn expired_reset_tokens_are_rejected() {
+        let api = api_with(|_, mail| mail.reset_token_ttl = 0);
+        api.login_as("jane@example.com", Role::User).await;
+        let (status, _) = api.call(warp::test::request().method("POST").path("/password/forgot").json(&json!({ "email": "jane@example.com" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        let token = api.mailed_token("Reset your password").await;
+
+        let reset = json!({ "token": token, "password": "a brand new passphrase" });
+        let (status, problem) = api.call(warp::test::request().method("POST").path("/password/reset").json(&reset)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(problem["code"], "invalid_email_token");
+        api.login("jane@example.com").await;
+    }
+
+    #[tokio::test]
+    async fn search_snippets_escape_the_text_around_the_marks() {
+        let api = api();
+        let token = api.login_as("jane@example.com", Role::User).await;
+        let question = json!({ "title": "Borrow <b>checker</b>", "content": "<script>alert(\"borrow\")</script> & more", "tags": ["rust"] });
+        let (status, _) = api.call(warp::test::request().method("POST").path("/questions").header("Authorization", &token).json(&question)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, hits) = api.call(warp::test::request().path("/search?q=borrow")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(hits[0]["title"], "Borrow <b>checker</b>");
+        assert_eq!(
+            hits[0]["snippet"],
+            "<mark>Borrow</mark> &lt;b&gt;checker&lt;/b&gt; <mark>&lt;script&gt;alert(&quot;borrow&quot;)&lt;/script&gt;</mark> &amp; more",
+        );
+    }
+
+    #[tokio::test]
+    async fn refresh_tokens_are_rotated() {
+        let api = api();
+        api.login_as("jane@example.com", Role::User).await;
+        let first = api.login("jane@example.com").await;
+
+        let (status, second) = api.refresh(&first["refresh_token"]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(second["refresh_token"], first["refresh_token"]);
+        assert_eq!(second["expires_in"], AuthConfig::default().access_token_ttl);
+        // still the same session, so the earlier access token keeps working as well
+        for token in [&first["access_token"], &second["access_token"]] {
+            assert_eq!(api.session_check(token.as_str().unwrap()).await.0, StatusCode::OK);
+        }
+        let (status, _) = api.refresh(&second["refresh_token"]).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn reusing_a_refresh_token_revokes_the_session() {
+        let api = api();
+        api.login_as("jane@example.com", Role::User).await;
+        let stolen = api.login("jane@example.com").await;
+        let other = api.login("jane@example.com").await;
+        let (_, rotated) = api.refresh(&stolen["refresh_token"]).await;
+
+        let (status, problem) = api.refresh(&stolen["refresh_token"]).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(problem["code"], "invalid_refresh_token");
+        // whoever holds the newer token is logged out too, the account's other sessions are not
+        let (status, _) = api.refresh(&rotated["refresh_token"]).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        for token in [&stolen["access_token"], &rotated["access_token"]] {
+            assert_eq!(api.session_check(token.as_str().unwrap()).await, (StatusCode::UNAUTHORIZED, json!("session_revoked")));
+        }
+        assert_eq!(api.session_check(other["access_token"].as_str().unwrap()).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn logout_ends_the_current_session_and_logout_all_every_session() {
+        let api = api();
+        api.login_as("jane@example.com", Role::User).await;
+        let phone = api.login("jane@example.com").await;
+        let laptop = api.login("jane@example.com").await;
+        let logout = |path: &str, session: &Value| warp::test::request().method("POST").path(path).header("Authorization", session["access_token"].as_str().unwrap());
+
+        let (status, _) = api.call(logout("/logout", &phone)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(api.session_check(phone["access_token"].as_str().unwrap()).await, (StatusCode::UNAUTHORIZED, json!("session_revoked")));
+        assert_eq!(api.refresh(&phone["refresh_token"]).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(api.session_check(laptop["access_token"].as_str().unwrap()).await.0, StatusCode::OK);
+
+        // the session `login_as` opened is still alive as well
+        let (status, reply) = api.call(logout("/logout-all", &laptop)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(reply, "Logged out of 2 sessions");
+        assert_eq!(api.session_check(laptop["access_token"].as_str().unwrap()).await, (StatusCode::UNAUTHORIZED, json!("session_revoked")));
+        assert_eq!(api.refresh(&laptop["refresh_token"]).await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn each_account_votes_once_and_may_change_its_mind() {
+        let api = api();
+        let author = api.login_as("jane@example.com", Role::User).await;
+        let john = api.login_as("john@example.com", Role::User).await;
+        let mary = api.login_as("mary@example.com", Role::User).await;
+        let question_id = api.add_question(&author, "Lifetimes").await;
+        let vote = |token: &str, direction: &str| warp::test::request().method("POST").path(&format!("/questions/{}/vote", question_id))
+            .header("Authorization", token).json(&json!({ "direction": direction }));
+
+        // voting again replaces the earlier vote instead of adding to it
+        assert_eq!(api.call(vote(&john, "up")).await, (StatusCode::OK, json!({ "score": 1 })));
+        assert_eq!(api.call(vote(&john, "up")).await, (StatusCode::OK, json!({ "score": 1 })));
+        assert_eq!(api.call(vote(&mary, "up")).await, (StatusCode::OK, json!({ "score": 2 })));
+        assert_eq!(api.call(vote(&john, "down")).await, (StatusCode::OK, json!({ "score": 0 })));
+        let (_, question) = api.call(warp::test::request().path(&format!("/questions/{}", question_id))).await;
+        assert_eq!(question["score"], 0);
+
+        let (status, score) = api.call(delete(&format!("/questions/{}/vote", question_id), &john)).await;
+        assert_eq!((status, score), (StatusCode::OK, json!({ "score": 1 })));
+        // withdrawing a vote that was never cast changes nothing
+        let (status, score) = api.call(delete(&format!("/questions/{}/vote", question_id), &john)).await;
+        assert_eq!((status, score), (StatusCode::OK, json!({ "score": 1 })));
+        let (_, questions) = api.call(warp::test::request().path("/questions")).await;
+        assert_eq!(questions[0]["score"], 1);
+    }
+
+    #[tokio::test]
+    async fn nobody_votes_on_their_own_content() {
+        let api = api();
+        let author = api.login_as("jane@example.com", Role::User).await;
+        let john = api.login_as("john@example.com", Role::User).await;
+        let question_id = api.add_question(&author, "Lifetimes").await;
+        let answer_id = api.add_answer(&john, question_id).await;
+        let vote = |path: String, token: &str| warp::test::request().method("POST").path(&path)
+            .header("Authorization", token).json(&json!({ "direction": "up" }));
+
+        let (status, _) = api.call(vote(format!("/questions/{}/vote", question_id), &author)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = api.call(vote(format!("/answers/{}/vote", answer_id), &john)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, score) = api.call(vote(format!("/answers/{}/vote", answer_id), &author)).await;
+        assert_eq!((status, score), (StatusCode::OK, json!({ "score": 1 })));
+        let (_, answer) = api.call(warp::test::request().path(&format!("/answers/{}", answer_id))).await;
+        assert_eq!(answer["score"], 1);
+        let (_, question) = api.call(warp::test::request().path(&format!("/questions/{}", question_id))).await;
+        assert_eq!(question["score"], 0);
+        assert_eq!(question["answers"][0]["score"], 1);
+    }
+
+    #[tokio::test]
+    async fn only_the_asker_accepts_an_answer_of_their_question() {
+        let api = api();
+        let author = api.login_as("jane@example.com", Role::User).await;
+        let john = api.login_as("john@example.com", Role::User).await;
+        let moderator = api.login_as("mod@example.com", Role::Moderator).await;
+        let question_id = api.add_question(&author, "Lifetimes").await;
+        let other_question_id = api.add_question(&author, "Traits").await;
+        let first = api.add_answer(&john, question_id).await;
+        let second = api.add_answer(&john, question_id).await;
+        let elsewhere = api.add_answer(&john, other_question_id).await;
+        let accept = |answer_id: i64, token: &str| warp::test::request().method("POST")
+            .path(&format!("/questions/{}/accept/{}", question_id, answer_id)).header("Authorization", token);
+
+        for token in [&john, &moderator] {
+            let (status, _) = api.call(accept(second, token)).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+        }
+        let (status, problem) = api.call(accept(elsewhere, &author)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(problem["errors"][0]["field"], "answer_id");
+
+        let (status, question) = api.call(accept(second, &author)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(question["accepted_answer_id"], second);
+        let (_, question) = api.call(warp::test::request().path(&format!("/questions/{}", question_id))).await;
+        let answer_ids: Vec<i64> = question["answers"].as_array().unwrap().iter().map(|answer| answer["id"].as_i64().unwrap()).collect();
+        assert_eq!(answer_ids, [second, first]);
+        assert_eq!(api.question_titles("answered=true").await, [json!("Lifetimes")]);
+        assert_eq!(api.question_titles("answered=false").await, [json!("Traits")]);
+
+        let (status, question) = api.call(delete(&format!("/questions/{}/accept", question_id), &author)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(question["accepted_answer_id"], Value::Null);
+        assert!(api.question_titles("answered=true").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn changing_the_password_ends_every_session() {
+        let api = api();
+        let token = api.login_as("jane@example.com", Role::User).await;
+        let other = api.login("jane@example.com").await;
+        let change = |current: &str, new: &str| put("/account/password", &token, &json!({ "current_password": current, "new_password": new }));
+
+        let (status, problem) = api.call(change("wrong password", "a brand new passphrase")).await;
+        assert_eq!((status, &problem["code"]), (StatusCode::UNAUTHORIZED, &json!("invalid_credentials")));
+        let (status, problem) = api.call(change(PASSWORD, "short")).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(problem["errors"][0]["field"], "new_password");
+
+        let (status, _) = api.call(change(PASSWORD, "a brand new passphrase")).await;
+        assert_eq!(status, StatusCode::OK);
+        for token in [token.as_str(), other["access_token"].as_str().unwrap()] {
+            assert_eq!(api.session_check(token).await, (StatusCode::UNAUTHORIZED, json!("session_revoked")));
+        }
+        let old = json!({ "email": "jane@example.com", "password": PASSWORD });
+        let (status, _) = api.call(warp::test::request().method("POST").path("/login").json(&old)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let new = json!({ "email": "jane@example.com", "password": "a brand new passphrase" });
+        let (status, _) = api.call(warp::test::request().method("POST").path("/login").json(&new)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn a_changed_email_has_to_be_verified_again() {
+        let api = api_with(|auth, _| auth.require_verified_email = true);
+        let account = json!({ "email": "jane@example.com", "password": PASSWORD });
+        let (status, _) = api.call(warp::test::request().method("POST").path("/registration").json(&account)).await;
+        assert_eq!(status, StatusCode::OK);
+        let token = api.mailed_token("Please verify your email address").await;
+        api.call(warp::test::request().method("POST").path("/email/verify").json(&json!({ "token": token }))).await;
+        let token = api.login("jane@example.com").await["access_token"].as_str().unwrap().to_owned();
+        let change = |email: &str, password: &str| put("/account/email", &token, &json!({ "email": email, "password": password }));
+
+        let (status, _) = api.call(change("jane@example.org", "wrong password")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, problem) = api.call(change("jane@example.com", PASSWORD)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(problem["errors"][0]["field"], "email");
+
+        let (status, _) = api.call(change("jane@example.org", PASSWORD)).await;
+        assert_eq!(status, StatusCode::OK);
+        // the session stays, logging in again waits for the new address
+        let (status, export) = api.call(warp::test::request().path("/account/export").header("Authorization", &token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(export["account"]["email"], "jane@example.org");
+        assert_eq!(export["email_verified"], false);
+        let (status, _) = api.call(warp::test::request().method("POST").path("/login").json(&account)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let moved = json!({ "email": "jane@example.org", "password": PASSWORD });
+        let (status, problem) = api.call(warp::test::request().method("POST").path("/login").json(&moved)).await;
+        assert_eq!((status, &problem["code"]), (StatusCode::FORBIDDEN, &json!("email_not_verified")));
+
+        let token = api.mailed_token("Please verify your email address").await;
+        let (status, _) = api.call(warp::test::request().method("POST").path("/email/verify").json(&json!({ "token": token }))).await;
+        assert_eq!(status, StatusCode::OK);
+        api.login("jane@example.org").await;
+    }
+
+    #[tokio::test]
+    async fn deleted_accounts_leave_their_content_anonymous_or_take_it_along() {
+        let api = api();
+        let jane = api.login_as("jane@example.com", Role::User).await;
+        let mary = api.login_as("mary@example.com", Role::User).await;
+        let john = api.login_as("john@example.com", Role::User).await;
+        let jane_id = api.account_id("jane@example.com").await;
+        let kept = api.add_question(&jane, "Lifetimes").await;
+        let kept_answer = api.add_answer(&john, kept).await;
+        let removed = api.add_question(&mary, "Traits").await;
+        let removed_answer = api.add_answer(&john, removed).await;
+        let remove = |token: &str, body: Value| warp::test::request().method("DELETE").path("/account").header("Authorization", token).json(&body);
+
+        let (status, _) = api.call(remove(&jane, json!({ "password": "wrong password" }))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        // anonymising is the default
+        let (status, _) = api.call(remove(&jane, json!({ "password": PASSWORD }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(api.session_check(&jane).await, (StatusCode::UNAUTHORIZED, json!("session_revoked")));
+        assert!(api.store.get_account("jane@example.com".to_owned()).await.is_err());
+        let (status, _) = api.call(warp::test::request().path(&format!("/questions/{}", kept))).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = api.call(warp::test::request().path(&format!("/answers/{}", kept_answer))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(api.question_titles(&format!("author={}", jane_id)).await.is_empty());
+
+        // the answers of others to the deleted questions go as well
+        let (status, _) = api.call(remove(&mary, json!({ "password": PASSWORD, "content": "delete" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = api.call(warp::test::request().path(&format!("/questions/{}", removed))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = api.call(warp::test::request().path(&format!("/answers/{}", removed_answer))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(api.question_titles("").await, [json!("Lifetimes")]);
+    }
+
+    #[tokio::test]
+    async fn the_export_holds_everything_stored_about_the_account() {
+        let api = api();
+        let jane = api.login_as("jane@example.com", Role::User).await;
+        let john = api.login_as("john@example.com", Role::User).await;
+        let asked = api.add_question(&jane, "Lifetimes").await;
+        let trashed = api.add_question(&jane, "Traits").await;
+        let other = api.add_question(&john, "Macros").await;
+        api.add_answer(&jane, other).await;
+        api.call(delete(&format!("/questions/{}", trashed), &jane)).await;
+        let vote = warp::test::request().method("POST").path(&format!("/questions/{}/vote", other))
+            .header("Authorization", &jane).json(&json!({ "direction": "down" }));
+        api.call(vote).await;
+
+        let res = warp::test::request().path("/account/export").header("Authorization", &jane).reply(&api.routes).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let jane_id = api.account_id("jane@example.com").await;
+        assert_eq!(res.headers()["content-disposition"], format!("attachment; filename=\"account-{}.json\"", jane_id));
+        let export: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(export["account"], json!({ "id": jane_id, "email": "jane@example.com", "role": "user" }));
+        assert_eq!(export["questions"][0]["id"], asked);
+        assert_eq!(export["questions"].as_array().unwrap().len(), 1);
+        assert_eq!(export["deleted_questions"][0]["id"], trashed);
+        assert_eq!(export["answers"][0]["question_id"], other);
+        assert_eq!(export["votes"], json!([{ "question_id": other, "answer_id": null, "direction": "down" }]));
+    }
+}
@@ -1,36 +1,77 @@
-use std::collections::HashMap;
-use warp::{http::StatusCode, Rejection, Reply};
+use warp::{http::{header::LINK, HeaderValue, StatusCode}, Rejection, Reply};
 use tracing::{event, instrument, Level};
+use handle_errors::FieldError; // internal library
 
 use crate::types::account::Session;
-use crate::store::Store;
-use crate::types::pagination;
-use crate::types::question::{Question, NewQuestion};
-use crate::profanity::check_profanity;
+use crate::store::DynStore;
+use crate::types::{filter, pagination};
+use crate::types::question::{NewQuestion, QuestionWithAnswers, UpdateQuestion};
+use crate::types::revision::{RevisionDiff, RevisionNote, RollbackRequest};
+use crate::profanity::{check_profanity, DynProfanityChecker};
 
 
+// the raw query string is parsed by hand because `tag` may be repeated
 #[instrument]
-pub async fn get_question(params: HashMap<String, String> , store: Store) -> Result<impl Reply, Rejection> {
+pub async fn get_question(raw_query: String, store: DynStore) -> Result<impl Reply, Rejection> {
     event!(target: "webapp_api", Level::INFO, "querying questions");
-    let mut pagination = pagination::Pagination::default();
-    if !params.is_empty() {
-        event!(Level::INFO, pagination = true);
-        pagination = pagination::get_pagination(params)?; // set Pagination value if not empty
+    let query = filter::get_question_query(&raw_query)?;
+    event!(Level::INFO, filter = ?query.filter, pagination = ?query.page);
+    match query.page {
+        // offset mode keeps returning a bare array for existing clients
+        pagination::Page::Offset(pagination) => match store.get_questions(&query.filter, pagination.limit, pagination.offset).await {
+            Ok(res) => Ok(warp::reply::json(&res).into_response()),
+            Err(e) => Err(warp::reject::custom(e)),
+        },
+        pagination::Page::Cursor(keyset) => match store.get_questions_page(&query.filter, &keyset).await {
+            Ok(res) => {
+                let link = link_header(&raw_query, &res);
+                let mut response = warp::reply::json(&res).into_response();
+                if let Some(link) = link {
+                    response.headers_mut().insert(LINK, link);
+                }
+                Ok(response)
+            },
+            Err(e) => Err(warp::reject::custom(e)),
+        },
     }
-    match store.get_questions(pagination.limit, pagination.offset).await {
-        Ok(res) => Ok(warp::reply::json(&res)),
-        Err(e) => Err(warp::reject::custom(e)),
+}
+
+// RFC 8288 links to the neighbouring pages, e.g. `</questions?tag=rust&limit=20&cursor=...>; rel="next"`;
+// every other parameter of the current request is kept
+fn link_header<T>(raw_query: &str, page: &pagination::KeysetPage<T>) -> Option<HeaderValue> {
+    let params: Vec<(String, String)> = serde_urlencoded::from_str::<Vec<(String, String)>>(raw_query).ok()?
+        .into_iter()
+        .filter(|(key, _)| key != "cursor")
+        .collect();
+    let links: Vec<String> = [(&page.next_cursor, "next"), (&page.prev_cursor, "prev")].into_iter()
+        .filter_map(|(cursor, rel)| {
+            let mut params = params.clone();
+            params.push(("cursor".to_owned(), cursor.as_ref()?.encode()));
+            let query = serde_urlencoded::to_string(&params).ok()?;
+            Some(format!("</questions?{}>; rel=\"{}\"", query, rel))
+        })
+        .collect();
+    if links.is_empty() {
+        return None;
     }
+    // the query is percent-encoded, so the value is always a valid header
+    HeaderValue::from_str(&links.join(", ")).ok()
+}
+
+pub async fn get_single_question(id: i32, store: DynStore) -> Result<impl Reply, Rejection> {
+    let question = store.get_question(id).await?;
+    let answers = store.get_answers(id, None, 0).await?;
+    Ok(warp::reply::json(&QuestionWithAnswers { question, answers }))
 }
 
 // NB: the order of the arguments also matter when passing it into the main function
-pub async fn add_question(session: Session, store: Store, new_question: NewQuestion) -> Result<impl Reply, Rejection> {
+pub async fn add_question(session: Session, store: DynStore, profanity: DynProfanityChecker, new_question: NewQuestion) -> Result<impl Reply, Rejection> {
     let account_id = session.account_id;
-    let title = match check_profanity(new_question.title).await {
+    let title = match check_profanity(profanity.as_ref(), new_question.title).await {
         Ok(res) => res,
         Err(e) => return Err(warp::reject::custom(e)),
     };
-    let content = match check_profanity(new_question.content).await {
+    let content = match check_profanity(profanity.as_ref(), new_question.content).await {
         Ok(res) => res,
         Err(e) => return Err(warp::reject::custom(e)),
     };
@@ -47,29 +88,36 @@ pub async fn add_question(session: Session, store: Store, new_question: NewQuest
     }
 }
 
-pub async fn update_question(id: i32, session: Session, store: Store, question: Question) -> Result<impl Reply, Rejection> {
+pub async fn update_question(id: i32, session: Session, store: DynStore, profanity: DynProfanityChecker, question: UpdateQuestion) -> Result<impl Reply, Rejection> {
     // get the `account_id` out of the `session_id` to be able to pass a reference to later functions
     let account_id = session.account_id;
-    // this store function checks if the original question was created by the same account
-    if store.is_question_owner(id, &account_id).await? {
-        let title = check_profanity(question.title);
-        let content = check_profanity(question.content);
+    // unknown questions are a 404 for everyone, not a 403 for all but moderators
+    store.get_question(id).await?;
+    // moderators may edit any question, everyone else only the questions their account created
+    if session.role.can_moderate() || store.is_question_owner(id, &account_id).await? {
+        let title = check_profanity(profanity.as_ref(), question.title);
+        let content = check_profanity(profanity.as_ref(), question.content);
 
         // run the API requests concurrently using `tokio::join`
         let (title, content) = tokio::join!(title, content);
-        if title.is_ok() && content.is_ok() {
-            let question = Question {
-                id: question.id,
-                title: title.unwrap(),
-                content: content.unwrap(),
-                tags: question.tags
-            };
-            match store.update_question(question, id, account_id).await {
-                Ok(res) => Ok(warp::reply::json(&res)),
-                Err(e) => Err(warp::reject::custom(e))
-            }
-        } else {
-            Err(warp::reject::custom(title.expect_err("Expected API call to have failed here.")))
+        match (title, content) {
+            (Ok(title), Ok(content)) => {
+                let edit = NewQuestion {
+                    title,
+                    content,
+                    tags: question.tags,
+                };
+                // every edit is kept as a new revision, moderator edits included
+                let note = RevisionNote {
+                    editor_id: account_id,
+                    reason: question.reason,
+                };
+                match store.update_question(edit, id, &note).await {
+                    Ok(res) => Ok(warp::reply::json(&res)),
+                    Err(e) => Err(warp::reject::custom(e))
+                }
+            },
+            (Err(e), _) | (_, Err(e)) => Err(warp::reject::custom(e)),
         }
     } else {
         // returns this error if the ID in the DB != ID in the session
@@ -77,9 +125,10 @@ pub async fn update_question(id: i32, session: Session, store: Store, question:
     }
 }
 
-pub async fn delete_question(id: i32, session: Session, store: Store) -> Result<impl Reply, Rejection> {
+pub async fn delete_question(id: i32, session: Session, store: DynStore) -> Result<impl Reply, Rejection> {
     let account_id = session.account_id;
-    if store.is_question_owner(id, &account_id).await? {
+    store.get_question(id).await?;
+    if session.role.can_moderate() || store.is_question_owner(id, &account_id).await? {
         match store.delete_question(id).await {
             Ok(_) => Ok(warp::reply::with_status(format!("Question {} deleted", id), StatusCode::OK)),
             Err(e) => Err(warp::reject::custom(e))
@@ -88,4 +137,90 @@ pub async fn delete_question(id: i32, session: Session, store: Store) -> Result<
         Err(warp::reject::custom(handle_errors::WarpError::Unauthorized))
     }
     
-}
\ No newline at end of file
+}
+
+// brings a deleted question back, as long as the purge job has not removed it yet
+pub async fn restore_question(id: i32, session: Session, store: DynStore) -> Result<impl Reply, Rejection> {
+    let account_id = session.account_id;
+    // only deleted questions can be restored, so live ones are a 404 as well
+    store.get_deleted_question(id).await?;
+    if session.role.can_moderate() || store.is_question_owner(id, &account_id).await? {
+        match store.restore_question(id).await {
+            Ok(question) => Ok(warp::reply::json(&question)),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(handle_errors::WarpError::Unauthorized))
+    }
+}
+// only the question's owner decides which answer solved it
+pub async fn accept_answer(id: i32, answer_id: i32, session: Session, store: DynStore) -> Result<impl Reply, Rejection> {
+    store.get_question(id).await?;
+    if !store.is_question_owner(id, &session.account_id).await? {
+        return Err(warp::reject::custom(handle_errors::WarpError::Unauthorized));
+    }
+    let answer = store.get_answer(answer_id).await?;
+    if answer.question_id.0 != id {
+        return Err(warp::reject::custom(handle_errors::WarpError::ValidationError(vec![
+            FieldError::new("answer_id", format!("answer {} does not belong to question {}", answer_id, id)),
+        ])));
+    }
+    match store.set_accepted_answer(id, Some(answer_id)).await {
+        Ok(question) => Ok(warp::reply::json(&question)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn unaccept_answer(id: i32, session: Session, store: DynStore) -> Result<impl Reply, Rejection> {
+    store.get_question(id).await?;
+    if !store.is_question_owner(id, &session.account_id).await? {
+        return Err(warp::reject::custom(handle_errors::WarpError::Unauthorized));
+    }
+    match store.set_accepted_answer(id, None).await {
+        Ok(question) => Ok(warp::reply::json(&question)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+// oldest first
+pub async fn get_revisions(id: i32, store: DynStore) -> Result<impl Reply, Rejection> {
+    store.get_question(id).await?;
+    match store.get_revisions(id).await {
+        Ok(revisions) => Ok(warp::reply::json(&revisions)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+// compares a revision with the one before it; revision 1 is compared with itself
+pub async fn get_revision_diff(id: i32, revision: i32, store: DynStore) -> Result<impl Reply, Rejection> {
+    store.get_question(id).await?;
+    let new = store.get_revision(id, revision).await?;
+    let old = match revision {
+        1 => new.clone(),
+        _ => store.get_revision(id, revision - 1).await?,
+    };
+    Ok(warp::reply::json(&RevisionDiff::between(&old, &new)))
+}
+
+// restores the title, content and tags of an earlier revision as a new revision,
+// so the rollback itself shows up in the history and can be undone
+pub async fn rollback_question(id: i32, revision: i32, session: Session, store: DynStore, request: RollbackRequest) -> Result<impl Reply, Rejection> {
+    let account_id = session.account_id;
+    if !(session.role.can_moderate() || store.is_question_owner(id, &account_id).await?) {
+        return Err(warp::reject::custom(handle_errors::WarpError::Unauthorized));
+    }
+    let old = store.get_revision(id, revision).await?;
+    let note = RevisionNote {
+        editor_id: account_id,
+        reason: Some(request.reason.unwrap_or_else(|| format!("Rolled back to revision {}", revision))),
+    };
+    let restored = NewQuestion {
+        title: old.title,
+        content: old.content,
+        tags: old.tags,
+    };
+    match store.update_question(restored, id, &note).await {
+        Ok(question) => Ok(warp::reply::json(&question)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
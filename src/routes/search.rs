@@ -0,0 +1,16 @@
+use std::collections::HashMap;
+use warp::{Rejection, Reply};
+use tracing::{event, instrument, Level};
+
+use crate::store::DynStore;
+use crate::types::search;
+
+#[instrument]
+pub async fn search(params: HashMap<String, String>, store: DynStore) -> Result<impl Reply, Rejection> {
+    let search = search::get_search(params)?;
+    event!(Level::INFO, query = %search.query, "searching questions and answers");
+    match store.search(&search).await {
+        Ok(hits) => Ok(warp::reply::json(&hits)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
@@ -0,0 +1,36 @@
+use warp::{Rejection, Reply};
+
+use crate::store::DynStore;
+use crate::types::account::Session;
+use crate::types::vote::{NewVote, Score, VoteTarget};
+
+pub async fn vote_question(id: i32, session: Session, store: DynStore, vote: NewVote) -> Result<impl Reply, Rejection> {
+    // unknown questions are a 404, and nobody votes up their own question
+    store.get_question(id).await?;
+    if store.is_question_owner(id, &session.account_id).await? {
+        return Err(warp::reject::custom(handle_errors::WarpError::Unauthorized));
+    }
+    let score = store.vote(VoteTarget::Question(id), &session.account_id, vote.direction).await?;
+    Ok(warp::reply::json(&Score { score }))
+}
+
+pub async fn unvote_question(id: i32, session: Session, store: DynStore) -> Result<impl Reply, Rejection> {
+    store.get_question(id).await?;
+    let score = store.remove_vote(VoteTarget::Question(id), &session.account_id).await?;
+    Ok(warp::reply::json(&Score { score }))
+}
+
+pub async fn vote_answer(id: i32, session: Session, store: DynStore, vote: NewVote) -> Result<impl Reply, Rejection> {
+    store.get_answer(id).await?;
+    if store.is_answer_owner(id, &session.account_id).await? {
+        return Err(warp::reject::custom(handle_errors::WarpError::Unauthorized));
+    }
+    let score = store.vote(VoteTarget::Answer(id), &session.account_id, vote.direction).await?;
+    Ok(warp::reply::json(&Score { score }))
+}
+
+pub async fn unvote_answer(id: i32, session: Session, store: DynStore) -> Result<impl Reply, Rejection> {
+    store.get_answer(id).await?;
+    let score = store.remove_vote(VoteTarget::Answer(id), &session.account_id).await?;
+    Ok(warp::reply::json(&Score { score }))
+}
@@ -0,0 +1,881 @@
+// in-process backend for tests and local demos; nothing survives a restart
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::Deserialize;
+use handle_errors::WarpError; // internal library
+
+use crate::store::{AccountStore, HealthStore, QuestionStore};
+use crate::types::{
+    answer::{Answer, AnswerId, NewAnswer},
+    question::{QuestionId, Question, NewQuestion},
+    search::{HitKind, Search, SearchHit},
+    pagination::{Direction, KeysetPage, KeysetPagination},
+    filter::{QuestionFilter, QuestionSort},
+    vote::{CastVote, VoteDirection, VoteTarget},
+    revision::{Revision, RevisionNote},
+    account::{Account, AccountContent, AccountExport, AccountId, AccountInfo, Role, SessionId, TokenPurpose}
+};
+
+#[derive(Debug)]
+struct QuestionRecord {
+    question: Question,
+    // seeded questions belong to nobody
+    account_id: Option<AccountId>,
+    // naive UTC, like the `TIMESTAMP` column
+    created_on: NaiveDateTime,
+    // set while the question sits in the trash
+    deleted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug)]
+struct AnswerRecord {
+    answer: Answer,
+    // `None` once the account was deleted and its content anonymized
+    account_id: Option<AccountId>,
+}
+
+#[derive(Debug)]
+struct SessionRecord {
+    account_id: AccountId,
+    refresh_token_hash: String,
+    previous_token_hash: Option<String>,
+    expires_on: DateTime<Utc>,
+    revoked: bool,
+}
+
+#[derive(Debug)]
+struct TokenRecord {
+    account_id: AccountId,
+    purpose: TokenPurpose,
+    token_hash: String,
+    expires_on: DateTime<Utc>,
+    used: bool,
+}
+
+impl SessionRecord {
+    fn is_active(&self) -> bool {
+        !self.revoked && self.expires_on > Utc::now()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Data {
+    questions: BTreeMap<i32, QuestionRecord>,
+    answers: BTreeMap<i32, AnswerRecord>,
+    // keyed by lowercase email, like the unique index on `LOWER(email)`
+    accounts: HashMap<String, Account>,
+    sessions: HashMap<i32, SessionRecord>,
+    tokens: Vec<TokenRecord>,
+    // +1 or -1 per account and target, like the `votes` table
+    votes: HashMap<(VoteTarget, AccountId), i16>,
+    // oldest first per question, like `question_revisions`
+    revisions: BTreeMap<i32, Vec<Revision>>,
+    last_question_id: i32,
+    last_answer_id: i32,
+    last_account_id: i32,
+    last_session_id: i32,
+}
+
+impl Data {
+    // questions that are not in the trash
+    fn live_question(&self, question_id: i32) -> Option<&QuestionRecord> {
+        self.questions.get(&question_id).filter(|record| record.deleted_at.is_none())
+    }
+
+    fn live_question_mut(&mut self, question_id: i32) -> Option<&mut QuestionRecord> {
+        self.questions.get_mut(&question_id).filter(|record| record.deleted_at.is_none())
+    }
+
+    // answers of deleted questions are gone along with the question
+    fn live_answer(&self, answer_id: i32) -> Option<&AnswerRecord> {
+        self.answers.get(&answer_id).filter(|record| self.live_question(record.answer.question_id.0).is_some())
+    }
+
+    // the stored questions and answers carry their score, so it is recomputed after every vote
+    fn refresh_score(&mut self, target: VoteTarget) -> i64 {
+        let score = self.votes.iter()
+            .filter(|((voted, _), _)| *voted == target)
+            .map(|(_, value)| *value as i64)
+            .sum();
+        match target {
+            VoteTarget::Question(id) => if let Some(record) = self.questions.get_mut(&id) {
+                record.question.score = score;
+            },
+            VoteTarget::Answer(id) => if let Some(record) = self.answers.get_mut(&id) {
+                record.answer.score = score;
+            },
+        }
+        score
+    }
+
+    // snapshots the current state of a question as its next revision
+    fn record_revision(&mut self, question_id: i32, editor_id: Option<AccountId>, reason: Option<String>) {
+        let Some(record) = self.questions.get(&question_id) else { return };
+        let revisions = self.revisions.entry(question_id).or_default();
+        revisions.push(Revision {
+            question_id: QuestionId(question_id),
+            revision: revisions.len() as i32 + 1,
+            title: record.question.title.clone(),
+            content: record.question.content.clone(),
+            tags: record.question.tags.clone(),
+            editor_id,
+            reason,
+            created_on: Utc::now().naive_utc(),
+        });
+    }
+
+    // like `ON DELETE CASCADE` on `votes` and `ON DELETE SET NULL` on `questions.accepted_answer_id`
+    fn remove_answer(&mut self, answer_id: i32) {
+        self.answers.remove(&answer_id);
+        self.votes.retain(|(target, _), _| *target != VoteTarget::Answer(answer_id));
+        for record in self.questions.values_mut() {
+            if record.question.accepted_answer_id == Some(AnswerId(answer_id)) {
+                record.question.accepted_answer_id = None;
+            }
+        }
+    }
+
+    fn answer_count(&self, question_id: i32) -> usize {
+        self.answers.values().filter(|record| record.answer.question_id.0 == question_id).count()
+    }
+
+    // the questions matching `filter`, in the order of its `sort`
+    fn filtered_questions(&self, filter: &QuestionFilter) -> Vec<&QuestionRecord> {
+        let mut records: Vec<&QuestionRecord> = self.questions.values()
+            .filter(|record| record.deleted_at.is_none())
+            .filter(|record| filter.tags.iter().all(|tag| record.question.tags.as_ref().is_some_and(|tags| tags.contains(tag))))
+            .filter(|record| filter.author.is_none() || record.account_id == filter.author)
+            .filter(|record| filter.created_after.is_none_or(|after| record.created_on >= after))
+            .filter(|record| filter.created_before.is_none_or(|before| record.created_on < before))
+            .filter(|record| !filter.unanswered || self.answer_count(record.question.id.0) == 0)
+            .filter(|record| filter.answered.is_none_or(|answered| record.question.accepted_answer_id.is_some() == answered))
+            .collect();
+        records.sort_by_key(|record| (record.created_on, record.question.id.0));
+        match filter.sort {
+            QuestionSort::Oldest => (),
+            QuestionSort::Newest => records.reverse(),
+            QuestionSort::MostAnswers => {
+                records.reverse();
+                // stable, so questions with equal counts stay newest first
+                records.sort_by_key(|record| std::cmp::Reverse(self.answer_count(record.question.id.0)));
+            },
+            QuestionSort::Votes => {
+                records.reverse();
+                records.sort_by_key(|record| std::cmp::Reverse(record.question.score));
+            },
+        }
+        records
+    }
+}
+
+// layout of the entries in `questions.json`; the IDs there are strings
+#[derive(Deserialize)]
+struct SeedQuestion {
+    id: String,
+    title: String,
+    content: String,
+    tags: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryStore {
+    data: Arc<RwLock<Data>>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a store pre-filled with the questions from a file like `questions.json`
+    pub fn from_seed_file(path: &Path) -> Result<Self, std::io::Error> {
+        let content = std::fs::read_to_string(path)?;
+        let seed: HashMap<String, SeedQuestion> = serde_json::from_str(&content)?;
+        let store = Self::new();
+        let created_on = Utc::now().naive_utc();
+        {
+            let mut data = store.write();
+            for question in seed.into_values() {
+                let id = question.id.parse::<i32>()
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                data.last_question_id = data.last_question_id.max(id);
+                data.questions.insert(id, QuestionRecord {
+                    question: Question {
+                        id: QuestionId(id),
+                        title: question.title,
+                        content: question.content,
+                        tags: question.tags,
+                        score: 0,
+                        accepted_answer_id: None,
+                    },
+                    account_id: None,
+                    created_on,
+                    deleted_at: None,
+                });
+                data.record_revision(id, None, None);
+            }
+        }
+        Ok(store)
+    }
+
+    // a poisoned lock only means another request panicked half-way; the maps are still usable
+    fn read(&self) -> RwLockReadGuard<'_, Data> {
+        self.data.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Data> {
+        self.data.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl QuestionStore for InMemoryStore {
+    async fn get_questions(&self, filter: &QuestionFilter, limit: Option<i32>, offset: i32) -> Result<Vec<Question>, WarpError> {
+        let data = self.read();
+        let questions = data.filtered_questions(filter).into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.map_or(usize::MAX, |limit| limit.max(0) as usize))
+            .map(|record| record.question.clone())
+            .collect();
+        Ok(questions)
+    }
+
+    async fn get_questions_page(&self, filter: &QuestionFilter, page: &KeysetPagination) -> Result<KeysetPage<Question>, WarpError> {
+        let data = self.read();
+        let mut records = data.filtered_questions(filter);
+        if let Some(cursor) = &page.cursor {
+            let position = (cursor.created_on, cursor.id);
+            // "after" means later in the listing, which runs backwards in time for `newest`
+            let later_in_time = (cursor.direction == Direction::After) != (filter.sort == QuestionSort::Newest);
+            records.retain(|record| {
+                let key = (record.created_on, record.question.id.0);
+                if later_in_time { key > position } else { key < position }
+            });
+            if cursor.direction == Direction::Before {
+                records.reverse();
+            }
+        }
+        let rows = records.into_iter()
+            .take(page.limit as usize + 1)
+            .map(|record| (record.question.clone(), record.created_on, record.question.id.0))
+            .collect();
+        Ok(KeysetPage::from_rows(rows, page))
+    }
+
+    async fn add_question(&self, new_question: NewQuestion, account_id: AccountId) -> Result<Question, WarpError> {
+        let mut data = self.write();
+        data.last_question_id += 1;
+        let question = Question {
+            id: QuestionId(data.last_question_id),
+            title: new_question.title,
+            content: new_question.content,
+            tags: new_question.tags,
+            score: 0,
+            accepted_answer_id: None,
+        };
+        data.questions.insert(question.id.0, QuestionRecord {
+            question: question.clone(),
+            account_id: Some(account_id.clone()),
+            created_on: Utc::now().naive_utc(),
+            deleted_at: None,
+        });
+        data.record_revision(question.id.0, Some(account_id), None);
+        Ok(question)
+    }
+
+    async fn update_question(&self, question: NewQuestion, question_id: i32, note: &RevisionNote) -> Result<Question, WarpError> {
+        let mut data = self.write();
+        let updated = match data.live_question_mut(question_id) {
+            Some(record) => {
+                record.question.title = question.title;
+                record.question.content = question.content;
+                record.question.tags = question.tags;
+                record.question.clone()
+            },
+            None => return Err(WarpError::NotFound),
+        };
+        data.record_revision(question_id, Some(note.editor_id.clone()), note.reason.clone());
+        Ok(updated)
+    }
+
+    async fn delete_question(&self, question_id: i32) -> Result<bool, WarpError> {
+        let mut data = self.write();
+        match data.live_question_mut(question_id) {
+            Some(record) => {
+                record.deleted_at = Some(Utc::now().naive_utc());
+                Ok(true)
+            },
+            None => Err(WarpError::NotFound),
+        }
+    }
+
+    async fn restore_question(&self, question_id: i32) -> Result<Question, WarpError> {
+        let mut data = self.write();
+        match data.questions.get_mut(&question_id) {
+            Some(record) if record.deleted_at.is_some() => {
+                record.deleted_at = None;
+                Ok(record.question.clone())
+            },
+            _ => Err(WarpError::NotFound),
+        }
+    }
+
+    async fn purge_deleted_questions(&self, deleted_before: NaiveDateTime) -> Result<u64, WarpError> {
+        let mut data = self.write();
+        let purged: Vec<i32> = data.questions.values()
+            .filter(|record| record.deleted_at.is_some_and(|deleted_at| deleted_at < deleted_before))
+            .map(|record| record.question.id.0)
+            .collect();
+        for question_id in &purged {
+            data.questions.remove(question_id);
+            data.revisions.remove(question_id);
+        }
+        data.answers.retain(|_, record| !purged.contains(&record.answer.question_id.0));
+        // like `ON DELETE CASCADE` on `votes`
+        let Data { votes, questions, answers, .. } = &mut *data;
+        votes.retain(|(target, _), _| match target {
+            VoteTarget::Question(id) => questions.contains_key(id),
+            VoteTarget::Answer(id) => answers.contains_key(id),
+        });
+        Ok(purged.len() as u64)
+    }
+
+    async fn add_answer(&self, new_answer: NewAnswer, account_id: AccountId) -> Result<Answer, WarpError> {
+        let mut data = self.write();
+        // mirrors the foreign key on `answers.corresponding_question`
+        if data.live_question(new_answer.question_id.0).is_none() {
+            return Err(WarpError::DatabaseQueryError(sqlx::Error::RowNotFound));
+        }
+        data.last_answer_id += 1;
+        let answer = Answer {
+            id: AnswerId(data.last_answer_id),
+            content: new_answer.content,
+            question_id: new_answer.question_id,
+            score: 0,
+        };
+        data.answers.insert(answer.id.0, AnswerRecord {
+            answer: answer.clone(),
+            account_id: Some(account_id),
+        });
+        Ok(answer)
+    }
+
+    async fn get_question(&self, question_id: i32) -> Result<Question, WarpError> {
+        self.read().live_question(question_id)
+            .map(|record| record.question.clone())
+            .ok_or(WarpError::NotFound)
+    }
+
+    async fn get_deleted_question(&self, question_id: i32) -> Result<Question, WarpError> {
+        self.read().questions.get(&question_id)
+            .filter(|record| record.deleted_at.is_some())
+            .map(|record| record.question.clone())
+            .ok_or(WarpError::NotFound)
+    }
+
+    async fn get_answers(&self, question_id: i32, limit: Option<i32>, offset: i32) -> Result<Vec<Answer>, WarpError> {
+        let data = self.read();
+        let accepted = data.questions.get(&question_id).and_then(|record| record.question.accepted_answer_id.clone());
+        let mut records: Vec<&AnswerRecord> = data.answers.values()
+            .filter(|record| record.answer.question_id.0 == question_id)
+            .collect();
+        // stable, so the rest stays oldest first
+        records.sort_by_key(|record| Some(&record.answer.id) != accepted.as_ref());
+        let answers = records.into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.map_or(usize::MAX, |limit| limit.max(0) as usize))
+            .map(|record| record.answer.clone())
+            .collect();
+        Ok(answers)
+    }
+
+    async fn get_answer(&self, answer_id: i32) -> Result<Answer, WarpError> {
+        self.read().live_answer(answer_id)
+            .map(|record| record.answer.clone())
+            .ok_or(WarpError::NotFound)
+    }
+
+    async fn get_revision(&self, question_id: i32, revision: i32) -> Result<Revision, WarpError> {
+        self.read().revisions.get(&question_id)
+            .and_then(|revisions| revisions.iter().find(|stored| stored.revision == revision))
+            .cloned()
+            .ok_or(WarpError::NotFound)
+    }
+
+    async fn get_revisions(&self, question_id: i32) -> Result<Vec<Revision>, WarpError> {
+        Ok(self.read().revisions.get(&question_id).cloned().unwrap_or_default())
+    }
+
+    async fn is_question_owner(&self, question_id: i32, account_id: &AccountId) -> Result<bool, WarpError> {
+        Ok(self.read().questions.get(&question_id)
+            .map_or(false, |record| record.account_id.as_ref() == Some(account_id)))
+    }
+
+    async fn update_answer(&self, content: String, answer_id: i32) -> Result<Answer, WarpError> {
+        let mut data = self.write();
+        if data.live_answer(answer_id).is_none() {
+            return Err(WarpError::NotFound);
+        }
+        match data.answers.get_mut(&answer_id) {
+            Some(record) => {
+                record.answer.content = content;
+                Ok(record.answer.clone())
+            },
+            None => Err(WarpError::NotFound),
+        }
+    }
+
+    async fn delete_answer(&self, answer_id: i32) -> Result<bool, WarpError> {
+        let mut data = self.write();
+        if data.live_answer(answer_id).is_none() {
+            return Err(WarpError::NotFound);
+        }
+        data.remove_answer(answer_id);
+        Ok(true)
+    }
+
+    async fn is_answer_owner(&self, answer_id: i32, account_id: &AccountId) -> Result<bool, WarpError> {
+        Ok(self.read().live_answer(answer_id)
+            .map_or(false, |record| record.account_id.as_ref() == Some(account_id)))
+    }
+
+    async fn set_accepted_answer(&self, question_id: i32, answer_id: Option<i32>) -> Result<Question, WarpError> {
+        let mut data = self.write();
+        match data.live_question_mut(question_id) {
+            Some(record) => {
+                record.question.accepted_answer_id = answer_id.map(AnswerId);
+                Ok(record.question.clone())
+            },
+            None => Err(WarpError::NotFound),
+        }
+    }
+
+    async fn vote(&self, target: VoteTarget, account_id: &AccountId, direction: VoteDirection) -> Result<i64, WarpError> {
+        let mut data = self.write();
+        // mirrors the foreign keys of the `votes` table
+        let exists = match target {
+            VoteTarget::Question(id) => data.live_question(id).is_some(),
+            VoteTarget::Answer(id) => data.answers.contains_key(&id),
+        };
+        if !exists {
+            return Err(WarpError::DatabaseQueryError(sqlx::Error::RowNotFound));
+        }
+        data.votes.insert((target, account_id.clone()), direction.value());
+        Ok(data.refresh_score(target))
+    }
+
+    async fn remove_vote(&self, target: VoteTarget, account_id: &AccountId) -> Result<i64, WarpError> {
+        let mut data = self.write();
+        data.votes.remove(&(target, account_id.clone()));
+        Ok(data.refresh_score(target))
+    }
+
+    async fn search(&self, search: &Search) -> Result<Vec<SearchHit>, WarpError> {
+        let terms = search_terms(&search.query);
+        if terms.is_empty() {
+            // a query of stop words and punctuation matches nothing in Postgres either
+            return Ok(Vec::new());
+        }
+        let data = self.read();
+        let has_tag = |question: &Question| match &search.tag {
+            Some(tag) => question.tags.as_ref().is_some_and(|tags| tags.contains(tag)),
+            None => true,
+        };
+
+        let questions = data.questions.values()
+            .filter(|record| record.deleted_at.is_none())
+            .filter(|record| has_tag(&record.question))
+            .filter(|record| search.author.is_none() || record.account_id == search.author)
+            .filter_map(|record| {
+                let question = &record.question;
+                // title matches count double, like the 'A' weight in the Postgres backend
+                let rank = 2 * match_count(&question.title, &terms)? + match_count(&question.content, &terms).unwrap_or(0);
+                let text = format!("{}\n{}", question.title, question.content);
+                Some(SearchHit {
+                    kind: HitKind::Question,
+                    question_id: question.id.clone(),
+                    answer_id: None,
+                    title: question.title.clone(),
+                    snippet: snippet(&text, &terms),
+                    rank: rank as f32,
+                })
+            });
+        let answers = data.answers.values()
+            .filter(|record| search.author.is_none() || record.account_id == search.author)
+            .filter_map(|record| {
+                let question = &data.live_question(record.answer.question_id.0)?.question;
+                if !has_tag(question) {
+                    return None;
+                }
+                Some(SearchHit {
+                    kind: HitKind::Answer,
+                    question_id: question.id.clone(),
+                    answer_id: Some(record.answer.id.clone()),
+                    title: question.title.clone(),
+                    snippet: snippet(&record.answer.content, &terms),
+                    rank: match_count(&record.answer.content, &terms)? as f32,
+                })
+            });
+
+        let mut hits: Vec<SearchHit> = questions.chain(answers).collect();
+        hits.sort_by(|a, b| b.rank.total_cmp(&a.rank)
+            .then(a.question_id.0.cmp(&b.question_id.0))
+            .then(a.answer_id.as_ref().map(|id| id.0).cmp(&b.answer_id.as_ref().map(|id| id.0))));
+        Ok(hits.into_iter()
+            .skip(search.offset.max(0) as usize)
+            .take(search.limit.max(0) as usize)
+            .collect())
+    }
+}
+
+// a rough stand-in for Postgres' text search: lowercase words, matched by prefix so that
+// "borrow" also finds "borrowing"; quotes, `or` and `-` are not supported
+fn search_terms(query: &str) -> Vec<String> {
+    words(query).map(str::to_lowercase).filter(|word| word != "or").collect()
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty())
+}
+
+fn is_match(word: &str, terms: &[String]) -> bool {
+    let word = word.to_lowercase();
+    terms.iter().any(|term| word.starts_with(term.as_str()))
+}
+
+// how often the terms occur in `text`, or `None` if any term is missing
+fn match_count(text: &str, terms: &[String]) -> Option<usize> {
+    let words: Vec<String> = words(text).map(str::to_lowercase).collect();
+    let mut count = 0;
+    for term in terms {
+        match words.iter().filter(|word| word.starts_with(term.as_str())).count() {
+            0 => return None,
+            n => count += n,
+        }
+    }
+    Some(count)
+}
+
+// up to 35 words starting a little before the first match, HTML-escaped like in Postgres and
+// matches wrapped in `<mark>`
+fn snippet(text: &str, terms: &[String]) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let first_match = words.iter()
+        .position(|word| words_match(word, terms))
+        .unwrap_or(0);
+    let start = first_match.saturating_sub(5);
+    words.iter()
+        .skip(start)
+        .take(35)
+        .map(|word| if words_match(word, terms) { format!("<mark>{}</mark>", html_escape(word)) } else { html_escape(word) })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// the same characters as the `html_escape` function of the migrations
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// whitespace-separated chunks may carry punctuation, e.g. "checker,"
+fn words_match(chunk: &str, terms: &[String]) -> bool {
+    words(chunk).any(|word| is_match(word, terms))
+}
+
+#[async_trait]
+impl AccountStore for InMemoryStore {
+    async fn add_account(&self, account: Account) -> Result<AccountId, WarpError> {
+        let mut data = self.write();
+        let key = account.email.to_lowercase();
+        if data.accounts.contains_key(&key) {
+            return Err(WarpError::AccountAlreadyExists);
+        }
+        data.last_account_id += 1;
+        let account_id = AccountId(data.last_account_id);
+        let account = Account {
+            id: Some(account_id.clone()),
+            ..account
+        };
+        data.accounts.insert(key, account);
+        Ok(account_id)
+    }
+
+    async fn get_account(&self, email: String) -> Result<Account, WarpError> {
+        self.read().accounts.get(&email.to_lowercase())
+            .cloned()
+            .ok_or(WarpError::NotFound)
+    }
+
+    async fn get_account_by_id(&self, account_id: &AccountId) -> Result<Account, WarpError> {
+        self.read().accounts.values()
+            .find(|account| account.id.as_ref() == Some(account_id))
+            .cloned()
+            .ok_or(WarpError::NotFound)
+    }
+
+    async fn create_session(&self, account_id: &AccountId, refresh_token_hash: &str, ttl_secs: u64) -> Result<SessionId, WarpError> {
+        let mut data = self.write();
+        data.last_session_id += 1;
+        let session_id = data.last_session_id;
+        data.sessions.insert(session_id, SessionRecord {
+            account_id: account_id.clone(),
+            refresh_token_hash: refresh_token_hash.to_owned(),
+            previous_token_hash: None,
+            expires_on: Utc::now() + Duration::seconds(ttl_secs as i64),
+            revoked: false,
+        });
+        Ok(SessionId(session_id))
+    }
+
+    async fn rotate_refresh_token(&self, session_id: &SessionId, old_hash: &str, new_hash: &str) -> Result<Option<(AccountId, Role)>, WarpError> {
+        let mut data = self.write();
+        let Data { sessions, accounts, .. } = &mut *data;
+        let session = match sessions.get_mut(&session_id.0) {
+            Some(session) if session.is_active() && session.refresh_token_hash == old_hash => session,
+            _ => return Ok(None),
+        };
+        // like the join in the Postgres backend, sessions of deleted accounts cannot be refreshed
+        match accounts.values().find(|account| account.id.as_ref() == Some(&session.account_id)) {
+            Some(account) => {
+                session.previous_token_hash = Some(std::mem::replace(&mut session.refresh_token_hash, new_hash.to_owned()));
+                Ok(Some((session.account_id.clone(), account.role)))
+            },
+            None => Ok(None),
+        }
+    }
+
+    async fn revoke_session_on_reuse(&self, session_id: &SessionId, reused_hash: &str) -> Result<bool, WarpError> {
+        let mut data = self.write();
+        match data.sessions.get_mut(&session_id.0) {
+            Some(session) if !session.revoked && session.previous_token_hash.as_deref() == Some(reused_hash) => {
+                session.revoked = true;
+                Ok(true)
+            },
+            _ => Ok(false),
+        }
+    }
+
+    async fn is_session_active(&self, session_id: &SessionId) -> Result<bool, WarpError> {
+        Ok(self.read().sessions.get(&session_id.0).map_or(false, SessionRecord::is_active))
+    }
+
+    async fn revoke_session(&self, session_id: &SessionId) -> Result<bool, WarpError> {
+        if let Some(session) = self.write().sessions.get_mut(&session_id.0) {
+            session.revoked = true;
+        }
+        Ok(true)
+    }
+
+    async fn revoke_account_sessions(&self, account_id: &AccountId) -> Result<u64, WarpError> {
+        let mut data = self.write();
+        let mut count = 0;
+        for session in data.sessions.values_mut().filter(|session| &session.account_id == account_id && !session.revoked) {
+            session.revoked = true;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    async fn get_accounts(&self, limit: Option<i32>, offset: i32) -> Result<Vec<AccountInfo>, WarpError> {
+        let data = self.read();
+        let mut accounts: Vec<AccountInfo> = data.accounts.values().filter_map(account_info).collect();
+        accounts.sort_by_key(|account| account.id.0);
+        Ok(accounts.into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.map_or(usize::MAX, |limit| limit.max(0) as usize))
+            .collect())
+    }
+
+    async fn set_account_role(&self, account_id: &AccountId, role: Role) -> Result<AccountInfo, WarpError> {
+        let mut data = self.write();
+        match data.accounts.values_mut().find(|account| account.id.as_ref() == Some(account_id)) {
+            Some(account) => {
+                account.role = role;
+                account_info(account).ok_or(WarpError::NotFound)
+            },
+            None => Err(WarpError::NotFound),
+        }
+    }
+
+    async fn delete_account(&self, account_id: &AccountId, content: AccountContent) -> Result<bool, WarpError> {
+        let mut data = self.write();
+        let before = data.accounts.len();
+        data.accounts.retain(|_, account| account.id.as_ref() != Some(account_id));
+        if data.accounts.len() == before {
+            return Err(WarpError::NotFound);
+        }
+        data.tokens.retain(|token| &token.account_id != account_id);
+        let voted: Vec<VoteTarget> = data.votes.keys()
+            .filter(|(_, voter)| voter == account_id)
+            .map(|(target, _)| *target)
+            .collect();
+        for target in voted {
+            data.votes.remove(&(target, account_id.clone()));
+            data.refresh_score(target);
+        }
+        for revision in data.revisions.values_mut().flatten().filter(|revision| revision.editor_id.as_ref() == Some(account_id)) {
+            revision.editor_id = None;
+        }
+
+        let owner = Some(account_id.clone());
+        match content {
+            AccountContent::Anonymize => {
+                for record in data.questions.values_mut().filter(|record| record.account_id == owner) {
+                    record.account_id = None;
+                }
+                for record in data.answers.values_mut().filter(|record| record.account_id == owner) {
+                    record.account_id = None;
+                }
+            },
+            AccountContent::Delete => {
+                let questions: Vec<i32> = data.questions.values()
+                    .filter(|record| record.account_id == owner)
+                    .map(|record| record.question.id.0)
+                    .collect();
+                let answers: Vec<i32> = data.answers.values()
+                    .filter(|record| record.account_id == owner || questions.contains(&record.answer.question_id.0))
+                    .map(|record| record.answer.id.0)
+                    .collect();
+                for answer_id in answers {
+                    data.remove_answer(answer_id);
+                }
+                for question_id in &questions {
+                    data.questions.remove(question_id);
+                    data.revisions.remove(question_id);
+                }
+                data.votes.retain(|(target, _), _| !matches!(target, VoteTarget::Question(id) if questions.contains(id)));
+            },
+        }
+        Ok(true)
+    }
+
+    async fn export_account(&self, account_id: &AccountId) -> Result<AccountExport, WarpError> {
+        let data = self.read();
+        let account = data.accounts.values()
+            .find(|account| account.id.as_ref() == Some(account_id))
+            .ok_or(WarpError::NotFound)?;
+        let owner = Some(account_id.clone());
+        let (deleted_questions, questions): (Vec<&QuestionRecord>, Vec<&QuestionRecord>) = data.questions.values()
+            .filter(|record| record.account_id == owner)
+            .partition(|record| record.deleted_at.is_some());
+        let mut votes: Vec<CastVote> = data.votes.iter()
+            .filter(|((_, voter), _)| voter == account_id)
+            .map(|((target, _), value)| CastVote {
+                question_id: match target { VoteTarget::Question(id) => Some(QuestionId(*id)), _ => None },
+                answer_id: match target { VoteTarget::Answer(id) => Some(AnswerId(*id)), _ => None },
+                direction: VoteDirection::from_value(*value),
+            })
+            .collect();
+        // votes carry no timestamp in here, so they are ordered by target
+        votes.sort_by_key(|vote| (vote.question_id.as_ref().map(|id| id.0), vote.answer_id.as_ref().map(|id| id.0)));
+        Ok(AccountExport {
+            account: AccountInfo {
+                id: account_id.clone(),
+                email: account.email.clone(),
+                role: account.role,
+            },
+            email_verified: account.email_verified,
+            questions: questions.into_iter().map(|record| record.question.clone()).collect(),
+            deleted_questions: deleted_questions.into_iter().map(|record| record.question.clone()).collect(),
+            answers: data.answers.values()
+                .filter(|record| record.account_id == owner)
+                .map(|record| record.answer.clone())
+                .collect(),
+            votes,
+            exported_on: Utc::now(),
+        })
+    }
+
+    async fn create_account_token(&self, account_id: &AccountId, purpose: TokenPurpose, token_hash: &str, ttl_secs: u64) -> Result<(), WarpError> {
+        self.write().tokens.push(TokenRecord {
+            account_id: account_id.clone(),
+            purpose,
+            token_hash: token_hash.to_owned(),
+            expires_on: Utc::now() + Duration::seconds(ttl_secs as i64),
+            used: false,
+        });
+        Ok(())
+    }
+
+    async fn use_account_token(&self, purpose: TokenPurpose, token_hash: &str) -> Result<Option<AccountId>, WarpError> {
+        let mut data = self.write();
+        let now = Utc::now();
+        let account_id = match data.tokens.iter()
+            .find(|token| token.token_hash == token_hash && token.purpose == purpose && !token.used && token.expires_on > now) {
+            Some(token) => token.account_id.clone(),
+            None => return Ok(None),
+        };
+        for token in data.tokens.iter_mut().filter(|token| token.account_id == account_id && token.purpose == purpose) {
+            token.used = true;
+        }
+        Ok(Some(account_id))
+    }
+
+    async fn set_email_verified(&self, account_id: &AccountId) -> Result<bool, WarpError> {
+        let mut data = self.write();
+        match data.accounts.values_mut().find(|account| account.id.as_ref() == Some(account_id)) {
+            Some(account) if !account.email_verified => {
+                account.email_verified = true;
+                Ok(true)
+            },
+            _ => Ok(false),
+        }
+    }
+
+    async fn set_password(&self, account_id: &AccountId, password_hash: &str) -> Result<bool, WarpError> {
+        let mut data = self.write();
+        match data.accounts.values_mut().find(|account| account.id.as_ref() == Some(account_id)) {
+            Some(account) => {
+                account.password = password_hash.to_owned();
+                Ok(true)
+            },
+            None => Err(WarpError::NotFound),
+        }
+    }
+
+    async fn set_email(&self, account_id: &AccountId, email: &str) -> Result<bool, WarpError> {
+        let mut data = self.write();
+        let old_email = match data.accounts.values().find(|account| account.id.as_ref() == Some(account_id)) {
+            Some(account) => account.email.clone(),
+            None => return Err(WarpError::NotFound),
+        };
+        let (old_key, key) = (old_email.to_lowercase(), email.to_lowercase());
+        if old_key != key && data.accounts.contains_key(&key) {
+            return Err(WarpError::AccountAlreadyExists);
+        }
+        // accounts are keyed by email, so the account moves to its new key
+        if let Some(mut account) = data.accounts.remove(&old_key) {
+            account.email = email.to_owned();
+            account.email_verified = false;
+            data.accounts.insert(key, account);
+        }
+        for token in data.tokens.iter_mut().filter(|token| &token.account_id == account_id) {
+            token.used = true;
+        }
+        Ok(true)
+    }
+}
+
+// always available, and there is no schema to migrate
+#[async_trait]
+impl HealthStore for InMemoryStore {
+    async fn ping(&self) -> Result<(), WarpError> {
+        Ok(())
+    }
+
+    async fn pending_migrations(&self) -> Result<Vec<i64>, WarpError> {
+        Ok(Vec::new())
+    }
+}
+
+// stored accounts always have an ID, it is assigned in `add_account`
+fn account_info(account: &Account) -> Option<AccountInfo> {
+    Some(AccountInfo {
+        id: account.id.clone()?,
+        email: account.email.clone(),
+        role: account.role,
+    })
+}
@@ -0,0 +1,223 @@
+// wraps any storage backend, records the duration of each of its methods in the metrics and
+// gives every call its own span beneath the request's
+use std::future::Future;
+use std::time::Instant;
+use async_trait::async_trait;
+use tracing::field::Empty;
+use tracing::Instrument;
+use chrono::NaiveDateTime;
+use handle_errors::WarpError; // internal library
+
+use crate::metrics::Metrics;
+use crate::store::{AccountStore, DynStore, HealthStore, QuestionStore};
+use crate::types::{
+    answer::{Answer, NewAnswer},
+    question::{Question, NewQuestion},
+    search::{Search, SearchHit},
+    pagination::{KeysetPage, KeysetPagination},
+    filter::QuestionFilter,
+    vote::{VoteDirection, VoteTarget},
+    revision::{Revision, RevisionNote},
+    account::{Account, AccountContent, AccountExport, AccountId, AccountInfo, Role, SessionId, TokenPurpose}
+};
+
+#[derive(Debug)]
+pub struct MeteredStore {
+    inner: DynStore,
+    metrics: Metrics,
+}
+
+impl MeteredStore {
+    pub fn new(inner: DynStore, metrics: Metrics) -> Self {
+        Self { inner, metrics }
+    }
+
+    async fn observe<T>(&self, operation: &'static str, call: impl Future<Output = Result<T, WarpError>>) -> Result<T, WarpError> {
+        let span = tracing::info_span!("store", otel.name = operation, otel.status_code = Empty);
+        let start = Instant::now();
+        let result = call.instrument(span.clone()).await;
+        self.metrics.observe_store(operation, result.is_ok(), start.elapsed());
+        if result.is_err() {
+            span.record("otel.status_code", "ERROR");
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl QuestionStore for MeteredStore {
+    async fn get_questions(&self, filter: &QuestionFilter, limit: Option<i32>, offset: i32) -> Result<Vec<Question>, WarpError> {
+        self.observe("get_questions", self.inner.get_questions(filter, limit, offset)).await
+    }
+
+    async fn get_questions_page(&self, filter: &QuestionFilter, page: &KeysetPagination) -> Result<KeysetPage<Question>, WarpError> {
+        self.observe("get_questions_page", self.inner.get_questions_page(filter, page)).await
+    }
+
+    async fn add_question(&self, new_question: NewQuestion, account_id: AccountId) -> Result<Question, WarpError> {
+        self.observe("add_question", self.inner.add_question(new_question, account_id)).await
+    }
+
+    async fn update_question(&self, question: NewQuestion, question_id: i32, note: &RevisionNote) -> Result<Question, WarpError> {
+        self.observe("update_question", self.inner.update_question(question, question_id, note)).await
+    }
+
+    async fn delete_question(&self, question_id: i32) -> Result<bool, WarpError> {
+        self.observe("delete_question", self.inner.delete_question(question_id)).await
+    }
+
+    async fn restore_question(&self, question_id: i32) -> Result<Question, WarpError> {
+        self.observe("restore_question", self.inner.restore_question(question_id)).await
+    }
+
+    async fn purge_deleted_questions(&self, deleted_before: NaiveDateTime) -> Result<u64, WarpError> {
+        self.observe("purge_deleted_questions", self.inner.purge_deleted_questions(deleted_before)).await
+    }
+
+    async fn add_answer(&self, new_answer: NewAnswer, account_id: AccountId) -> Result<Answer, WarpError> {
+        self.observe("add_answer", self.inner.add_answer(new_answer, account_id)).await
+    }
+
+    async fn get_question(&self, question_id: i32) -> Result<Question, WarpError> {
+        self.observe("get_question", self.inner.get_question(question_id)).await
+    }
+
+    async fn get_deleted_question(&self, question_id: i32) -> Result<Question, WarpError> {
+        self.observe("get_deleted_question", self.inner.get_deleted_question(question_id)).await
+    }
+
+    async fn get_answers(&self, question_id: i32, limit: Option<i32>, offset: i32) -> Result<Vec<Answer>, WarpError> {
+        self.observe("get_answers", self.inner.get_answers(question_id, limit, offset)).await
+    }
+
+    async fn get_answer(&self, answer_id: i32) -> Result<Answer, WarpError> {
+        self.observe("get_answer", self.inner.get_answer(answer_id)).await
+    }
+
+    async fn get_revision(&self, question_id: i32, revision: i32) -> Result<Revision, WarpError> {
+        self.observe("get_revision", self.inner.get_revision(question_id, revision)).await
+    }
+
+    async fn get_revisions(&self, question_id: i32) -> Result<Vec<Revision>, WarpError> {
+        self.observe("get_revisions", self.inner.get_revisions(question_id)).await
+    }
+
+    async fn is_question_owner(&self, question_id: i32, account_id: &AccountId) -> Result<bool, WarpError> {
+        self.observe("is_question_owner", self.inner.is_question_owner(question_id, account_id)).await
+    }
+
+    async fn update_answer(&self, content: String, answer_id: i32) -> Result<Answer, WarpError> {
+        self.observe("update_answer", self.inner.update_answer(content, answer_id)).await
+    }
+
+    async fn delete_answer(&self, answer_id: i32) -> Result<bool, WarpError> {
+        self.observe("delete_answer", self.inner.delete_answer(answer_id)).await
+    }
+
+    async fn is_answer_owner(&self, answer_id: i32, account_id: &AccountId) -> Result<bool, WarpError> {
+        self.observe("is_answer_owner", self.inner.is_answer_owner(answer_id, account_id)).await
+    }
+
+    async fn set_accepted_answer(&self, question_id: i32, answer_id: Option<i32>) -> Result<Question, WarpError> {
+        self.observe("set_accepted_answer", self.inner.set_accepted_answer(question_id, answer_id)).await
+    }
+
+    async fn vote(&self, target: VoteTarget, account_id: &AccountId, direction: VoteDirection) -> Result<i64, WarpError> {
+        self.observe("vote", self.inner.vote(target, account_id, direction)).await
+    }
+
+    async fn remove_vote(&self, target: VoteTarget, account_id: &AccountId) -> Result<i64, WarpError> {
+        self.observe("remove_vote", self.inner.remove_vote(target, account_id)).await
+    }
+
+    async fn search(&self, search: &Search) -> Result<Vec<SearchHit>, WarpError> {
+        self.observe("search", self.inner.search(search)).await
+    }
+}
+
+#[async_trait]
+impl AccountStore for MeteredStore {
+    async fn add_account(&self, account: Account) -> Result<AccountId, WarpError> {
+        self.observe("add_account", self.inner.add_account(account)).await
+    }
+
+    async fn get_account(&self, email: String) -> Result<Account, WarpError> {
+        self.observe("get_account", self.inner.get_account(email)).await
+    }
+
+    async fn get_account_by_id(&self, account_id: &AccountId) -> Result<Account, WarpError> {
+        self.observe("get_account_by_id", self.inner.get_account_by_id(account_id)).await
+    }
+
+    async fn create_session(&self, account_id: &AccountId, refresh_token_hash: &str, ttl_secs: u64) -> Result<SessionId, WarpError> {
+        self.observe("create_session", self.inner.create_session(account_id, refresh_token_hash, ttl_secs)).await
+    }
+
+    async fn rotate_refresh_token(&self, session_id: &SessionId, old_hash: &str, new_hash: &str) -> Result<Option<(AccountId, Role)>, WarpError> {
+        self.observe("rotate_refresh_token", self.inner.rotate_refresh_token(session_id, old_hash, new_hash)).await
+    }
+
+    async fn revoke_session_on_reuse(&self, session_id: &SessionId, reused_hash: &str) -> Result<bool, WarpError> {
+        self.observe("revoke_session_on_reuse", self.inner.revoke_session_on_reuse(session_id, reused_hash)).await
+    }
+
+    async fn is_session_active(&self, session_id: &SessionId) -> Result<bool, WarpError> {
+        self.observe("is_session_active", self.inner.is_session_active(session_id)).await
+    }
+
+    async fn revoke_session(&self, session_id: &SessionId) -> Result<bool, WarpError> {
+        self.observe("revoke_session", self.inner.revoke_session(session_id)).await
+    }
+
+    async fn revoke_account_sessions(&self, account_id: &AccountId) -> Result<u64, WarpError> {
+        self.observe("revoke_account_sessions", self.inner.revoke_account_sessions(account_id)).await
+    }
+
+    async fn get_accounts(&self, limit: Option<i32>, offset: i32) -> Result<Vec<AccountInfo>, WarpError> {
+        self.observe("get_accounts", self.inner.get_accounts(limit, offset)).await
+    }
+
+    async fn set_account_role(&self, account_id: &AccountId, role: Role) -> Result<AccountInfo, WarpError> {
+        self.observe("set_account_role", self.inner.set_account_role(account_id, role)).await
+    }
+
+    async fn delete_account(&self, account_id: &AccountId, content: AccountContent) -> Result<bool, WarpError> {
+        self.observe("delete_account", self.inner.delete_account(account_id, content)).await
+    }
+
+    async fn export_account(&self, account_id: &AccountId) -> Result<AccountExport, WarpError> {
+        self.observe("export_account", self.inner.export_account(account_id)).await
+    }
+
+    async fn create_account_token(&self, account_id: &AccountId, purpose: TokenPurpose, token_hash: &str, ttl_secs: u64) -> Result<(), WarpError> {
+        self.observe("create_account_token", self.inner.create_account_token(account_id, purpose, token_hash, ttl_secs)).await
+    }
+
+    async fn use_account_token(&self, purpose: TokenPurpose, token_hash: &str) -> Result<Option<AccountId>, WarpError> {
+        self.observe("use_account_token", self.inner.use_account_token(purpose, token_hash)).await
+    }
+
+    async fn set_email_verified(&self, account_id: &AccountId) -> Result<bool, WarpError> {
+        self.observe("set_email_verified", self.inner.set_email_verified(account_id)).await
+    }
+
+    async fn set_password(&self, account_id: &AccountId, password_hash: &str) -> Result<bool, WarpError> {
+        self.observe("set_password", self.inner.set_password(account_id, password_hash)).await
+    }
+
+    async fn set_email(&self, account_id: &AccountId, email: &str) -> Result<bool, WarpError> {
+        self.observe("set_email", self.inner.set_email(account_id, email)).await
+    }
+}
+
+// probes are not requests; kept out of the metrics, so they don't skew the latencies
+#[async_trait]
+impl HealthStore for MeteredStore {
+    async fn ping(&self) -> Result<(), WarpError> {
+        self.inner.ping().await
+    }
+
+    async fn pending_migrations(&self) -> Result<Vec<i64>, WarpError> {
+        self.inner.pending_migrations().await
+    }
+}
@@ -0,0 +1,130 @@
+// storage interface used by all routes; `postgres::Store` is the production backend,
+// `memory::InMemoryStore` keeps everything in process for tests and local demos
+use std::fmt::Debug;
+use std::sync::Arc;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use handle_errors::WarpError; // internal library
+
+use crate::types::{
+    answer::{Answer, NewAnswer},
+    question::{Question, NewQuestion},
+    search::{Search, SearchHit},
+    pagination::{KeysetPage, KeysetPagination},
+    filter::QuestionFilter,
+    vote::{VoteDirection, VoteTarget},
+    revision::{Revision, RevisionNote},
+    account::{Account, AccountContent, AccountExport, AccountId, AccountInfo, Role, SessionId, TokenPurpose}
+};
+
+pub mod postgres;
+#[cfg(feature = "in-memory")]
+pub mod memory;
+pub mod metered;
+
+pub use postgres::Store;
+pub use metered::MeteredStore;
+#[cfg(feature = "in-memory")]
+pub use memory::InMemoryStore;
+
+#[async_trait]
+pub trait QuestionStore {
+    // both listings apply the filter's conditions and its sort order, ties broken by `(created_on, id)`
+    async fn get_questions(&self, filter: &QuestionFilter, limit: Option<i32>, offset: i32) -> Result<Vec<Question>, WarpError>;
+    // only for sort orders where `QuestionSort::supports_cursor()` holds
+    async fn get_questions_page(&self, filter: &QuestionFilter, page: &KeysetPagination) -> Result<KeysetPage<Question>, WarpError>;
+    async fn add_question(&self, new_question: NewQuestion, account_id: AccountId) -> Result<Question, WarpError>;
+    // ownership (or moderator rights) is checked by the route before any update or delete;
+    // the first version of a question and every update are recorded as revisions; `NotFound` for
+    // unknown and deleted questions
+    async fn update_question(&self, question: NewQuestion, question_id: i32, note: &RevisionNote) -> Result<Question, WarpError>;
+    // soft delete: the question disappears from every listing and getter but can be restored
+    // until `purge_deleted_questions` removes it for good; `NotFound` if already deleted
+    async fn delete_question(&self, question_id: i32) -> Result<bool, WarpError>;
+    // `NotFound` unless the question is deleted and not yet purged
+    async fn restore_question(&self, question_id: i32) -> Result<Question, WarpError>;
+    // removes questions deleted before the given time together with their answers, votes and revisions;
+    // returns how many questions were purged
+    async fn purge_deleted_questions(&self, deleted_before: NaiveDateTime) -> Result<u64, WarpError>;
+    async fn add_answer(&self, new_answer: NewAnswer, account_id: AccountId) -> Result<Answer, WarpError>;
+    // the getters below fail with `WarpError::NotFound` for unknown IDs
+    async fn get_question(&self, question_id: i32) -> Result<Question, WarpError>;
+    // like `restore_question`, `NotFound` unless the question is deleted and not yet purged
+    async fn get_deleted_question(&self, question_id: i32) -> Result<Question, WarpError>;
+    // the accepted answer first, then oldest first
+    async fn get_answers(&self, question_id: i32, limit: Option<i32>, offset: i32) -> Result<Vec<Answer>, WarpError>;
+    async fn get_answer(&self, answer_id: i32) -> Result<Answer, WarpError>;
+    async fn get_revision(&self, question_id: i32, revision: i32) -> Result<Revision, WarpError>;
+    // oldest first
+    async fn get_revisions(&self, question_id: i32) -> Result<Vec<Revision>, WarpError>;
+    // also holds for deleted questions, so their owners can restore them
+    async fn is_question_owner(&self, question_id: i32, account_id: &AccountId) -> Result<bool, WarpError>;
+    // `NotFound` for unknown answers and answers of deleted questions
+    async fn update_answer(&self, content: String, answer_id: i32) -> Result<Answer, WarpError>;
+    async fn delete_answer(&self, answer_id: i32) -> Result<bool, WarpError>;
+    async fn is_answer_owner(&self, answer_id: i32, account_id: &AccountId) -> Result<bool, WarpError>;
+    // `None` un-accepts; the route makes sure the answer belongs to the question
+    async fn set_accepted_answer(&self, question_id: i32, answer_id: Option<i32>) -> Result<Question, WarpError>;
+    // voting again replaces the account's earlier vote on the target; both return the new score
+    async fn vote(&self, target: VoteTarget, account_id: &AccountId, direction: VoteDirection) -> Result<i64, WarpError>;
+    async fn remove_vote(&self, target: VoteTarget, account_id: &AccountId) -> Result<i64, WarpError>;
+    // full-text search over question titles, question content and answer content, best match first
+    async fn search(&self, search: &Search) -> Result<Vec<SearchHit>, WarpError>;
+}
+
+#[async_trait]
+pub trait AccountStore {
+    async fn add_account(&self, account: Account) -> Result<AccountId, WarpError>;
+    // `WarpError::NotFound` for unknown emails
+    async fn get_account(&self, email: String) -> Result<Account, WarpError>;
+    // `WarpError::NotFound` for unknown IDs
+    async fn get_account_by_id(&self, account_id: &AccountId) -> Result<Account, WarpError>;
+    // the refresh token itself never reaches the store, only its hash
+    async fn create_session(&self, account_id: &AccountId, refresh_token_hash: &str, ttl_secs: u64) -> Result<SessionId, WarpError>;
+    // swaps the current refresh token for a new one; returns `None` if the presented token
+    // is not the current one of a live session. The role is read fresh for the new access token
+    async fn rotate_refresh_token(&self, session_id: &SessionId, old_hash: &str, new_hash: &str) -> Result<Option<(AccountId, Role)>, WarpError>;
+    // a refresh token that was already rotated out is being used again, so it (or its
+    // successor) was stolen; revoke the whole session. Returns whether that happened
+    async fn revoke_session_on_reuse(&self, session_id: &SessionId, reused_hash: &str) -> Result<bool, WarpError>;
+    async fn is_session_active(&self, session_id: &SessionId) -> Result<bool, WarpError>;
+    async fn revoke_session(&self, session_id: &SessionId) -> Result<bool, WarpError>;
+    async fn revoke_account_sessions(&self, account_id: &AccountId) -> Result<u64, WarpError>;
+    // account management for admins; the two below fail with `WarpError::NotFound` for unknown IDs
+    async fn get_accounts(&self, limit: Option<i32>, offset: i32) -> Result<Vec<AccountInfo>, WarpError>;
+    async fn set_account_role(&self, account_id: &AccountId, role: Role) -> Result<AccountInfo, WarpError>;
+    // the account's votes go with it, its questions and answers are either anonymized or deleted;
+    // `WarpError::NotFound` for unknown IDs
+    async fn delete_account(&self, account_id: &AccountId, content: AccountContent) -> Result<bool, WarpError>;
+    // questions in the trash are included, answers to deleted questions too
+    async fn export_account(&self, account_id: &AccountId) -> Result<AccountExport, WarpError>;
+    // tokens sent by email; like refresh tokens, only their hash reaches the store
+    async fn create_account_token(&self, account_id: &AccountId, purpose: TokenPurpose, token_hash: &str, ttl_secs: u64) -> Result<(), WarpError>;
+    // uses up an unused, unexpired token together with every other open token of the same purpose
+    // for the account, so an older email can't be used afterwards; `None` if there is no such token
+    async fn use_account_token(&self, purpose: TokenPurpose, token_hash: &str) -> Result<Option<AccountId>, WarpError>;
+    async fn set_email_verified(&self, account_id: &AccountId) -> Result<bool, WarpError>;
+    async fn set_password(&self, account_id: &AccountId, password_hash: &str) -> Result<bool, WarpError>;
+    // marks the account as unverified and uses up its open tokens, so no email sent to the old
+    // address works anymore; fails like `add_account` if another account has the address
+    async fn set_email(&self, account_id: &AccountId, email: &str) -> Result<bool, WarpError>;
+}
+
+// what `/health/ready` checks on the backend
+#[async_trait]
+pub trait HealthStore: Send + Sync + Debug {
+    // a single round trip to the backend
+    async fn ping(&self) -> Result<(), WarpError>;
+    // versions of the migrations built into the binary that are not applied yet
+    async fn pending_migrations(&self) -> Result<Vec<i64>, WarpError>;
+}
+
+/// Everything a route handler may need from the storage backend
+pub trait Storage: QuestionStore + AccountStore + HealthStore + Send + Sync + Debug {}
+
+impl<T: QuestionStore + AccountStore + HealthStore + Send + Sync + Debug> Storage for T {}
+
+// what the warp filters hand to the route handlers; cheap to clone
+pub type DynStore = Arc<dyn Storage>;
+// all the readiness checks need; a `DynStore` converts into it
+pub type DynHealthStore = Arc<dyn HealthStore>;
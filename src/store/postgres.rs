@@ -0,0 +1,1107 @@
+// Postgres backend; handles all DB connections for all routes
+use std::time::Duration;
+use sqlx::migrate::Migrator;
+use sqlx::postgres::{PgConnection, PgPoolOptions, PgPool, PgRow};
+use sqlx::{Connection, Postgres, QueryBuilder, Row, Transaction};
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use handle_errors::WarpError; // internal Library
+
+use crate::config::DatabaseConfig;
+use crate::store::{AccountStore, HealthStore, QuestionStore};
+use crate::types::{
+    answer::{Answer, AnswerId, NewAnswer},
+    question::{QuestionId, Question, NewQuestion},
+    search::{HitKind, Search, SearchHit},
+    pagination::{Direction, KeysetPage, KeysetPagination},
+    filter::{QuestionFilter, QuestionSort},
+    vote::{CastVote, VoteDirection, VoteTarget},
+    revision::{Revision, RevisionNote},
+    account::{Account, AccountContent, AccountExport, AccountId, AccountInfo, Role, SessionId, TokenPurpose}
+};
+
+// a host that drops packets would otherwise keep a startup attempt waiting for minutes
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// the files in `migrations/`, built into the binary
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+// how `ts_headline` cuts and marks up search snippets; the text is HTML-escaped first, so the
+// `<mark>` tags are the only markup in them
+const HEADLINE_OPTIONS: &str = "StartSel=<mark>, StopSel=</mark>, MaxWords=35, MinWords=15, MaxFragments=2, FragmentDelimiter=\" … \"";
+
+// columns of every query that returns questions or answers; the score is summed up from `votes`
+const QUESTION_COLUMNS: &str = "id, title, content, tags, accepted_answer_id,
+    COALESCE((SELECT SUM(value) FROM votes WHERE votes.question_id = questions.id), 0) AS score";
+const ANSWER_COLUMNS: &str = "id, content, corresponding_question,
+    COALESCE((SELECT SUM(value) FROM votes WHERE votes.answer_id = answers.id), 0) AS score";
+
+#[derive(Clone, Debug)]
+pub struct Store {
+    pub conn: PgPool
+}
+
+impl Store {
+    pub async fn new(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        wait_for_database(config).await?;
+        let db_pool = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect(&config.url)
+            .await?;
+        Ok(Self {
+            conn: db_pool
+        })
+    }
+
+    async fn score(&self, target: VoteTarget) -> Result<i64, WarpError> {
+        match sqlx::query(&format!("SELECT COALESCE(SUM(value), 0) AS score FROM votes WHERE {} = $1", vote_column(target)))
+            .bind(vote_target_id(target))
+            .map(|row: PgRow| row.get("score"))
+            .fetch_one(&self.conn)
+            .await {
+                Ok(score) => Ok(score),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    // the question and its first revision are written together
+    async fn insert_question(&self, new_question: NewQuestion, account_id: &AccountId, note: &RevisionNote) -> Result<Question, sqlx::Error> {
+        let mut tx = self.conn.begin().await?;
+        let question = sqlx::query(&format!("INSERT INTO questions (title, content, tags, account_id)
+                            VALUES ($1, $2, $3, $4)
+                            RETURNING {}", QUESTION_COLUMNS
+            ))
+            .bind(new_question.title)
+            .bind(new_question.content)
+            .bind(new_question.tags)
+            .bind(account_id.0)
+            .map(|row: PgRow| question_from_row(&row))
+            .fetch_one(&mut tx)
+            .await?;
+        record_revision(&mut tx, question.id.0, note).await?;
+        tx.commit().await?;
+        Ok(question)
+    }
+
+    // the update locks the question row, so concurrent edits get consecutive revision numbers
+    async fn edit_question(&self, question: NewQuestion, question_id: i32, note: &RevisionNote) -> Result<Question, sqlx::Error> {
+        let mut tx = self.conn.begin().await?;
+        let question = sqlx::query(&format!("UPDATE questions
+                            SET title = $1, content = $2, tags = $3
+                            WHERE id = $4 AND deleted_at IS NULL
+                            RETURNING {}
+            ", QUESTION_COLUMNS))
+            .bind(question.title)
+            .bind(question.content)
+            .bind(question.tags)
+            .bind(question_id)
+            .map(|row: PgRow| question_from_row(&row))
+            .fetch_one(&mut tx)
+            .await?;
+        record_revision(&mut tx, question_id, note).await?;
+        tx.commit().await?;
+        Ok(question)
+    }
+
+    // the first update locks the token row, so a token used twice at the same time only works once
+    async fn consume_token(&self, purpose: TokenPurpose, token_hash: &str) -> Result<Option<AccountId>, sqlx::Error> {
+        let mut tx = self.conn.begin().await?;
+        let account_id = sqlx::query("UPDATE account_tokens SET used_on = NOW()
+                            WHERE token_hash = $1 AND purpose = $2 AND used_on IS NULL AND expires_on > NOW()
+                            RETURNING account_id")
+            .bind(token_hash)
+            .bind(purpose.as_str())
+            .map(|row: PgRow| AccountId(row.get("account_id")))
+            .fetch_optional(&mut tx)
+            .await?;
+        if let Some(account_id) = &account_id {
+            sqlx::query("UPDATE account_tokens SET used_on = NOW() WHERE account_id = $1 AND purpose = $2 AND used_on IS NULL")
+                .bind(account_id.0)
+                .bind(purpose.as_str())
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(account_id)
+    }
+
+    // `answers` has no `ON DELETE CASCADE`, so the answers go first; votes and revisions cascade.
+    // The questions stay locked until the commit, so a concurrent restore waits and then finds nothing
+    async fn purge_questions(&self, deleted_before: NaiveDateTime) -> Result<u64, sqlx::Error> {
+        let mut tx = self.conn.begin().await?;
+        let ids: Vec<i32> = sqlx::query("SELECT id FROM questions WHERE deleted_at < $1 FOR UPDATE")
+            .bind(deleted_before)
+            .map(|row: PgRow| row.get("id"))
+            .fetch_all(&mut tx)
+            .await?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        sqlx::query("DELETE FROM answers WHERE corresponding_question = ANY($1)")
+            .bind(&ids)
+            .execute(&mut tx)
+            .await?;
+        let purged = sqlx::query("DELETE FROM questions WHERE id = ANY($1)")
+            .bind(&ids)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(purged)
+    }
+
+    // votes and revision authorship go either way; like in `purge_questions`, answers to deleted
+    // questions have to go first. Nothing is committed for an unknown account
+    async fn remove_account(&self, account_id: &AccountId, content: AccountContent) -> Result<bool, sqlx::Error> {
+        let mut tx = self.conn.begin().await?;
+        sqlx::query("DELETE FROM votes WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("UPDATE question_revisions SET editor_id = NULL WHERE editor_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await?;
+        let statements = match content {
+            AccountContent::Anonymize => [
+                "UPDATE answers SET account_id = NULL WHERE account_id = $1",
+                "UPDATE questions SET account_id = NULL WHERE account_id = $1",
+            ],
+            AccountContent::Delete => [
+                "DELETE FROM answers WHERE account_id = $1
+                    OR corresponding_question IN (SELECT id FROM questions WHERE account_id = $1)",
+                "DELETE FROM questions WHERE account_id = $1",
+            ],
+        };
+        for statement in statements {
+            sqlx::query(statement)
+                .bind(account_id.0)
+                .execute(&mut tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM account_tokens WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await?;
+        let deleted = sqlx::query("DELETE FROM accounts WHERE id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await?
+            .rows_affected() > 0;
+        if deleted {
+            tx.commit().await?;
+        }
+        Ok(deleted)
+    }
+
+    // reads from a single snapshot, so the parts of the export agree with each other
+    async fn collect_export(&self, account_id: &AccountId) -> Result<AccountExport, sqlx::Error> {
+        let mut tx = self.conn.begin().await?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+            .execute(&mut tx)
+            .await?;
+        let account = sqlx::query("SELECT * FROM accounts WHERE id = $1")
+            .bind(account_id.0)
+            .try_map(|row: PgRow| account_from_row(&row))
+            .fetch_one(&mut tx)
+            .await?;
+        let questions: Vec<(Question, bool)> = sqlx::query(&format!("SELECT {}, deleted_at IS NOT NULL AS deleted
+                            FROM questions WHERE account_id = $1 ORDER BY created_on, id", QUESTION_COLUMNS))
+            .bind(account_id.0)
+            .map(|row: PgRow| (question_from_row(&row), row.get("deleted")))
+            .fetch_all(&mut tx)
+            .await?;
+        let answers = sqlx::query(&format!("SELECT {} FROM answers WHERE account_id = $1 ORDER BY created_on, id", ANSWER_COLUMNS))
+            .bind(account_id.0)
+            .map(|row: PgRow| answer_from_row(&row))
+            .fetch_all(&mut tx)
+            .await?;
+        let votes = sqlx::query("SELECT question_id, answer_id, value FROM votes WHERE account_id = $1 ORDER BY created_on, id")
+            .bind(account_id.0)
+            .map(|row: PgRow| CastVote {
+                question_id: row.get::<Option<i32>, _>("question_id").map(QuestionId),
+                answer_id: row.get::<Option<i32>, _>("answer_id").map(AnswerId),
+                direction: VoteDirection::from_value(row.get("value")),
+            })
+            .fetch_all(&mut tx)
+            .await?;
+        tx.commit().await?;
+        let (deleted_questions, questions): (Vec<_>, Vec<_>) = questions.into_iter().partition(|(_, deleted)| *deleted);
+        Ok(AccountExport {
+            account: AccountInfo {
+                id: account_id.clone(),
+                email: account.email,
+                role: account.role,
+            },
+            email_verified: account.email_verified,
+            questions: questions.into_iter().map(|(question, _)| question).collect(),
+            deleted_questions: deleted_questions.into_iter().map(|(question, _)| question).collect(),
+            answers,
+            votes,
+            exported_on: Utc::now(),
+        })
+    }
+}
+
+#[async_trait]
+impl QuestionStore for Store {
+    // `offset` indicates where to start querying;
+    // `limit` gives us the number of result we want
+    // offset = no to start questions from e.g. 50;; limit = no of questions to get e.g. 10
+    // if offset =50, limit=10....questions returned will be from 50 + 10 = questions 50 - 59
+    async fn get_questions(&self, filter: &QuestionFilter, limit: Option<i32>, offset: i32) -> Result<Vec<Question>, WarpError> {
+        let mut query = QueryBuilder::new(format!("SELECT {} FROM questions WHERE deleted_at IS NULL", QUESTION_COLUMNS));
+        push_question_filter(&mut query, filter);
+        query.push(match filter.sort {
+            QuestionSort::Oldest => " ORDER BY created_on, id",
+            QuestionSort::Newest => " ORDER BY created_on DESC, id DESC",
+            QuestionSort::MostAnswers => " ORDER BY (SELECT COUNT(*) FROM answers WHERE corresponding_question = questions.id) DESC,
+                                          created_on DESC, id DESC",
+            QuestionSort::Votes => " ORDER BY score DESC, created_on DESC, id DESC",
+        });
+        query.push(" LIMIT ").push_bind(limit).push(" OFFSET ").push_bind(offset);
+
+        match query.build()
+            .map(|row: PgRow| question_from_row(&row)) // use MAP to get rows returned by PG, and create a Question from it
+            .fetch_all(&self.conn)
+            .await {
+                Ok(questions) => Ok(questions),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+        }
+    }
+
+    // keyset pagination: the row-value comparison uses an index scan on `(created_on, id)`
+    // instead of counting through every skipped row like `OFFSET`
+    async fn get_questions_page(&self, filter: &QuestionFilter, page: &KeysetPagination) -> Result<KeysetPage<Question>, WarpError> {
+        let mut query = QueryBuilder::new(format!("SELECT {}, created_on FROM questions WHERE deleted_at IS NULL", QUESTION_COLUMNS));
+        push_question_filter(&mut query, filter);
+        // "after" means later in the listing, which runs backwards in time for `newest`
+        let ascending = match page.cursor.as_ref().map(|cursor| cursor.direction) {
+            None | Some(Direction::After) => filter.sort != QuestionSort::Newest,
+            Some(Direction::Before) => filter.sort == QuestionSort::Newest,
+        };
+        if let Some(cursor) = &page.cursor {
+            query.push(if ascending { " AND (created_on, id) > (" } else { " AND (created_on, id) < (" })
+                .push_bind(cursor.created_on)
+                .push(", ")
+                .push_bind(cursor.id)
+                .push(")");
+        }
+        query.push(if ascending { " ORDER BY created_on, id" } else { " ORDER BY created_on DESC, id DESC" });
+        // one row more than asked for, to find out whether there is another page
+        query.push(" LIMIT ").push_bind(page.limit + 1);
+
+        match query.build()
+            .map(|row: PgRow| (question_from_row(&row), row.get("created_on"), row.get("id")))
+            .fetch_all(&self.conn)
+            .await {
+                Ok(rows) => Ok(KeysetPage::from_rows(rows, page)),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn add_question(&self, new_question: NewQuestion, account_id: AccountId) -> Result<Question, WarpError> {
+        let note = RevisionNote { editor_id: account_id.clone(), reason: None };
+        match self.insert_question(new_question, &account_id, &note).await {
+            Ok(question) => Ok(question),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(WarpError::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn update_question(&self, question: NewQuestion, question_id: i32, note: &RevisionNote) -> Result<Question, WarpError> {
+        match self.edit_question(question, question_id, note).await {
+            Ok(question) => Ok(question),
+            // unknown or deleted
+            Err(sqlx::Error::RowNotFound) => Err(WarpError::NotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(WarpError::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    // only marks the question; answers, votes and revisions stay until `purge_deleted_questions`
+    async fn delete_question(&self, question_id: i32) -> Result<bool, WarpError> {
+        match sqlx::query("UPDATE questions SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL")
+            .bind(question_id)
+            .execute(&self.conn)
+            .await {
+                Ok(res) if res.rows_affected() == 0 => Err(WarpError::NotFound),
+                Ok(_) => Ok(true),
+                Err(e) => { 
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e)) 
+                }
+            }
+    }
+
+    async fn restore_question(&self, question_id: i32) -> Result<Question, WarpError> {
+        match sqlx::query(&format!("UPDATE questions SET deleted_at = NULL
+                            WHERE id = $1 AND deleted_at IS NOT NULL
+                            RETURNING {}", QUESTION_COLUMNS
+            ))
+            .bind(question_id)
+            .map(|row: PgRow| question_from_row(&row))
+            .fetch_one(&self.conn)
+            .await {
+                Ok(question) => Ok(question),
+                Err(sqlx::Error::RowNotFound) => Err(WarpError::NotFound),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn purge_deleted_questions(&self, deleted_before: NaiveDateTime) -> Result<u64, WarpError> {
+        match self.purge_questions(deleted_before).await {
+            Ok(purged) => Ok(purged),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(WarpError::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn add_answer(&self, new_answer: NewAnswer, account_id: AccountId) -> Result<Answer, WarpError> {
+        match sqlx::query(&format!(
+                "INSERT INTO answers (content, corresponding_question, account_id) VALUES ($1, $2, $3)
+                 RETURNING {}", ANSWER_COLUMNS
+            ))
+            .bind(new_answer.content)
+            .bind(new_answer.question_id.0)
+            .bind(account_id.0)
+            .map(|row: PgRow| answer_from_row(&row))
+            .fetch_one(&self.conn)
+            .await {
+                Ok(answer) => Ok(answer),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                },
+            }
+    }
+
+    async fn get_question(&self, question_id: i32) -> Result<Question, WarpError> {
+        match sqlx::query(&format!("SELECT {} FROM questions WHERE id = $1 AND deleted_at IS NULL", QUESTION_COLUMNS))
+            .bind(question_id)
+            .map(|row: PgRow| question_from_row(&row))
+            .fetch_one(&self.conn)
+            .await {
+                Ok(question) => Ok(question),
+                Err(sqlx::Error::RowNotFound) => Err(WarpError::NotFound),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn get_deleted_question(&self, question_id: i32) -> Result<Question, WarpError> {
+        match sqlx::query(&format!("SELECT {} FROM questions WHERE id = $1 AND deleted_at IS NOT NULL", QUESTION_COLUMNS))
+            .bind(question_id)
+            .map(|row: PgRow| question_from_row(&row))
+            .fetch_one(&self.conn)
+            .await {
+                Ok(question) => Ok(question),
+                Err(sqlx::Error::RowNotFound) => Err(WarpError::NotFound),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn get_answers(&self, question_id: i32, limit: Option<i32>, offset: i32) -> Result<Vec<Answer>, WarpError> {
+        match sqlx::query(&format!("SELECT {} FROM answers
+                            WHERE corresponding_question = $1
+                            ORDER BY id IS NOT DISTINCT FROM (SELECT accepted_answer_id FROM questions WHERE questions.id = $1) DESC,
+                                created_on, id
+                            LIMIT $2 OFFSET $3", ANSWER_COLUMNS
+            ))
+            .bind(question_id)
+            .bind(limit)
+            .bind(offset)
+            .map(|row: PgRow| answer_from_row(&row))
+            .fetch_all(&self.conn)
+            .await {
+                Ok(answers) => Ok(answers),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn get_answer(&self, answer_id: i32) -> Result<Answer, WarpError> {
+        // answers of deleted questions are gone along with the question
+        match sqlx::query(&format!("SELECT {} FROM answers
+                            WHERE id = $1
+                                AND EXISTS (SELECT 1 FROM questions WHERE questions.id = corresponding_question AND deleted_at IS NULL)
+            ", ANSWER_COLUMNS))
+            .bind(answer_id)
+            .map(|row: PgRow| answer_from_row(&row))
+            .fetch_one(&self.conn)
+            .await {
+                Ok(answer) => Ok(answer),
+                Err(sqlx::Error::RowNotFound) => Err(WarpError::NotFound),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn get_revision(&self, question_id: i32, revision: i32) -> Result<Revision, WarpError> {
+        match sqlx::query("SELECT * FROM question_revisions WHERE question_id = $1 AND revision = $2")
+            .bind(question_id)
+            .bind(revision)
+            .map(|row: PgRow| revision_from_row(&row))
+            .fetch_one(&self.conn)
+            .await {
+                Ok(revision) => Ok(revision),
+                Err(sqlx::Error::RowNotFound) => Err(WarpError::NotFound),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn get_revisions(&self, question_id: i32) -> Result<Vec<Revision>, WarpError> {
+        match sqlx::query("SELECT * FROM question_revisions WHERE question_id = $1 ORDER BY revision")
+            .bind(question_id)
+            .map(|row: PgRow| revision_from_row(&row))
+            .fetch_all(&self.conn)
+            .await {
+                Ok(revisions) => Ok(revisions),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn is_question_owner(&self, question_id: i32, account_id: &AccountId) -> Result<bool, WarpError> {
+        match sqlx::query("SELECT * from questions where id = $1 and account_id = $2")
+            .bind(question_id)
+            .bind(account_id.0)
+            .fetch_optional(&self.conn)
+            .await {
+                Ok(question) => Ok(question.is_some()),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn update_answer(&self, content: String, answer_id: i32) -> Result<Answer, WarpError> {
+        // answers of deleted questions stay as they are, like in `get_answer`
+        match sqlx::query(&format!("UPDATE answers
+                            SET content = $1
+                            WHERE id = $2
+                                AND EXISTS (SELECT 1 FROM questions WHERE questions.id = corresponding_question AND deleted_at IS NULL)
+                            RETURNING {}
+        ", ANSWER_COLUMNS))
+        .bind(content)
+        .bind(answer_id)
+        .map(|row: PgRow| answer_from_row(&row))
+        .fetch_one(&self.conn)
+        .await {
+            Ok(answer) => Ok(answer),
+            Err(sqlx::Error::RowNotFound) => Err(WarpError::NotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(WarpError::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn delete_answer(&self, answer_id: i32) -> Result<bool, WarpError> {
+        match sqlx::query("DELETE FROM answers
+                            WHERE id = $1
+                                AND EXISTS (SELECT 1 FROM questions WHERE questions.id = corresponding_question AND deleted_at IS NULL)
+            ")
+            .bind(answer_id)
+            .execute(&self.conn)
+            .await {
+                Ok(res) if res.rows_affected() == 0 => Err(WarpError::NotFound),
+                Ok(_) => Ok(true),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn is_answer_owner(&self, answer_id: i32, account_id: &AccountId) -> Result<bool, WarpError> {
+        match sqlx::query("SELECT * FROM answers
+                            WHERE id = $1 AND account_id = $2
+                                AND EXISTS (SELECT 1 FROM questions WHERE questions.id = corresponding_question AND deleted_at IS NULL)
+            ")
+            .bind(answer_id)
+            .bind(account_id.0)
+            .fetch_optional(&self.conn)
+            .await {
+                Ok(answer) => Ok(answer.is_some()),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn set_accepted_answer(&self, question_id: i32, answer_id: Option<i32>) -> Result<Question, WarpError> {
+        match sqlx::query(&format!("UPDATE questions SET accepted_answer_id = $1 WHERE id = $2 AND deleted_at IS NULL RETURNING {}", QUESTION_COLUMNS))
+            .bind(answer_id)
+            .bind(question_id)
+            .map(|row: PgRow| question_from_row(&row))
+            .fetch_one(&self.conn)
+            .await {
+                Ok(question) => Ok(question),
+                Err(sqlx::Error::RowNotFound) => Err(WarpError::NotFound),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn vote(&self, target: VoteTarget, account_id: &AccountId, direction: VoteDirection) -> Result<i64, WarpError> {
+        let column = vote_column(target);
+        // the conflict target has to repeat the predicate of the partial unique index
+        match sqlx::query(&format!("INSERT INTO votes (account_id, {0}, value) VALUES ($1, $2, $3)
+                            ON CONFLICT ({0}, account_id) WHERE {0} IS NOT NULL
+                            DO UPDATE SET value = EXCLUDED.value, created_on = NOW()", column
+            ))
+            .bind(account_id.0)
+            .bind(vote_target_id(target))
+            .bind(direction.value())
+            .execute(&self.conn)
+            .await {
+                Ok(_) => self.score(target).await,
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn remove_vote(&self, target: VoteTarget, account_id: &AccountId) -> Result<i64, WarpError> {
+        match sqlx::query(&format!("DELETE FROM votes WHERE {} = $1 AND account_id = $2", vote_column(target)))
+            .bind(vote_target_id(target))
+            .bind(account_id.0)
+            .execute(&self.conn)
+            .await {
+                Ok(_) => self.score(target).await,
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    // `websearch_to_tsquery` never fails on user input, unlike `to_tsquery`
+    async fn search(&self, search: &Search) -> Result<Vec<SearchHit>, WarpError> {
+        match sqlx::query("WITH query AS (SELECT websearch_to_tsquery('english', $1) AS q)
+                            SELECT kind, question_id, answer_id, title, snippet, rank FROM (
+                                SELECT 'question' AS kind, questions.id AS question_id, NULL::integer AS answer_id, title,
+                                    ts_headline('english', html_escape(title || E'\n' || content), query.q, $6) AS snippet,
+                                    ts_rank(search_vector, query.q) AS rank
+                                FROM questions, query
+                                WHERE search_vector @@ query.q
+                                    AND deleted_at IS NULL
+                                    AND ($2::text IS NULL OR $2 = ANY(tags))
+                                    AND ($3::integer IS NULL OR account_id = $3)
+                                UNION ALL
+                                SELECT 'answer', questions.id, answers.id, questions.title,
+                                    ts_headline('english', html_escape(answers.content), query.q, $6),
+                                    ts_rank(answers.search_vector, query.q)
+                                FROM answers JOIN questions ON questions.id = answers.corresponding_question, query
+                                WHERE answers.search_vector @@ query.q
+                                    AND questions.deleted_at IS NULL
+                                    AND ($2::text IS NULL OR $2 = ANY(questions.tags))
+                                    AND ($3::integer IS NULL OR answers.account_id = $3)
+                            ) AS hits
+                            ORDER BY rank DESC, question_id, answer_id NULLS FIRST
+                            LIMIT $4 OFFSET $5"
+            )
+            .bind(&search.query)
+            .bind(&search.tag)
+            .bind(search.author.as_ref().map(|author| author.0))
+            .bind(search.limit)
+            .bind(search.offset)
+            .bind(HEADLINE_OPTIONS)
+            .map(|row: PgRow| SearchHit {
+                kind: if row.get::<&str, _>("kind") == "answer" { HitKind::Answer } else { HitKind::Question },
+                question_id: QuestionId(row.get("question_id")),
+                answer_id: row.get::<Option<i32>, _>("answer_id").map(AnswerId),
+                title: row.get("title"),
+                snippet: row.get("snippet"),
+                rank: row.get("rank"),
+            })
+            .fetch_all(&self.conn)
+            .await {
+                Ok(hits) => Ok(hits),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+}
+
+#[async_trait]
+impl AccountStore for Store {
+    async fn add_account(&self, account: Account) -> Result<AccountId, WarpError> {
+        match sqlx::query(
+            "INSERT INTO accounts (email, password) VALUES ($1, $2) RETURNING id"
+        )
+        .bind(account.email)
+        .bind(account.password)
+        .map(|row: PgRow| AccountId(row.get("id")))
+        .fetch_one(&self.conn)
+        .await {
+            Ok(account_id) => Ok(account_id),
+            Err(e) if is_unique_violation(&e) => Err(WarpError::AccountAlreadyExists),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(WarpError::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn get_account(&self, email: String) -> Result<Account, WarpError> {
+        // served by the unique index on `LOWER(email)`
+        match sqlx::query("SELECT * FROM accounts WHERE LOWER(email) = LOWER($1)")
+            .bind(email)
+            .try_map(|row: PgRow| account_from_row(&row))
+            .fetch_one(&self.conn)
+            .await {
+                Ok(account) => Ok(account),
+                Err(sqlx::Error::RowNotFound) => Err(WarpError::NotFound),
+                Err(error) => {
+                    tracing::event!(tracing::Level::ERROR, "{}", error);
+                    Err(WarpError::DatabaseQueryError(error))
+                }
+            }
+    }
+
+    async fn get_account_by_id(&self, account_id: &AccountId) -> Result<Account, WarpError> {
+        match sqlx::query("SELECT * FROM accounts WHERE id = $1")
+            .bind(account_id.0)
+            .try_map(|row: PgRow| account_from_row(&row))
+            .fetch_one(&self.conn)
+            .await {
+                Ok(account) => Ok(account),
+                Err(sqlx::Error::RowNotFound) => Err(WarpError::NotFound),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn create_session(&self, account_id: &AccountId, refresh_token_hash: &str, ttl_secs: u64) -> Result<SessionId, WarpError> {
+        match sqlx::query("INSERT INTO sessions (account_id, refresh_token_hash, expires_on)
+                            VALUES ($1, $2, NOW() + make_interval(secs => $3))
+                            RETURNING id"
+            )
+            .bind(account_id.0)
+            .bind(refresh_token_hash)
+            .bind(ttl_secs as f64)
+            .map(|row: PgRow| SessionId(row.get("id")))
+            .fetch_one(&self.conn)
+            .await {
+                Ok(session_id) => Ok(session_id),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn rotate_refresh_token(&self, session_id: &SessionId, old_hash: &str, new_hash: &str) -> Result<Option<(AccountId, Role)>, WarpError> {
+        match sqlx::query("UPDATE sessions
+                            SET previous_token_hash = refresh_token_hash, refresh_token_hash = $3, refreshed_on = NOW()
+                            FROM accounts
+                            WHERE sessions.id = $1 AND accounts.id = sessions.account_id
+                                AND refresh_token_hash = $2 AND revoked_on IS NULL AND expires_on > NOW()
+                            RETURNING sessions.account_id, accounts.role"
+            )
+            .bind(session_id.0)
+            .bind(old_hash)
+            .bind(new_hash)
+            .try_map(|row: PgRow| Ok((AccountId(row.get("account_id")), role_from_row(&row)?)))
+            .fetch_optional(&self.conn)
+            .await {
+                Ok(account_id) => Ok(account_id),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn revoke_session_on_reuse(&self, session_id: &SessionId, reused_hash: &str) -> Result<bool, WarpError> {
+        match sqlx::query("UPDATE sessions SET revoked_on = NOW()
+                            WHERE id = $1 AND previous_token_hash = $2 AND revoked_on IS NULL"
+            )
+            .bind(session_id.0)
+            .bind(reused_hash)
+            .execute(&self.conn)
+            .await {
+                Ok(res) => Ok(res.rows_affected() > 0),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn is_session_active(&self, session_id: &SessionId) -> Result<bool, WarpError> {
+        match sqlx::query("SELECT id FROM sessions WHERE id = $1 AND revoked_on IS NULL AND expires_on > NOW()")
+            .bind(session_id.0)
+            .fetch_optional(&self.conn)
+            .await {
+                Ok(session) => Ok(session.is_some()),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn revoke_session(&self, session_id: &SessionId) -> Result<bool, WarpError> {
+        match sqlx::query("UPDATE sessions SET revoked_on = NOW() WHERE id = $1 AND revoked_on IS NULL")
+            .bind(session_id.0)
+            .execute(&self.conn)
+            .await {
+                Ok(_) => Ok(true),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn revoke_account_sessions(&self, account_id: &AccountId) -> Result<u64, WarpError> {
+        match sqlx::query("UPDATE sessions SET revoked_on = NOW() WHERE account_id = $1 AND revoked_on IS NULL")
+            .bind(account_id.0)
+            .execute(&self.conn)
+            .await {
+                Ok(res) => Ok(res.rows_affected()),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn get_accounts(&self, limit: Option<i32>, offset: i32) -> Result<Vec<AccountInfo>, WarpError> {
+        match sqlx::query("SELECT id, email, role FROM accounts ORDER BY id LIMIT $1 OFFSET $2")
+            .bind(limit)
+            .bind(offset)
+            .try_map(account_info_from_row)
+            .fetch_all(&self.conn)
+            .await {
+                Ok(accounts) => Ok(accounts),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn set_account_role(&self, account_id: &AccountId, role: Role) -> Result<AccountInfo, WarpError> {
+        match sqlx::query("UPDATE accounts SET role = $1 WHERE id = $2 RETURNING id, email, role")
+            .bind(role.as_str())
+            .bind(account_id.0)
+            .try_map(account_info_from_row)
+            .fetch_one(&self.conn)
+            .await {
+                Ok(account) => Ok(account),
+                Err(sqlx::Error::RowNotFound) => Err(WarpError::NotFound),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn delete_account(&self, account_id: &AccountId, content: AccountContent) -> Result<bool, WarpError> {
+        match self.remove_account(account_id, content).await {
+            Ok(false) => Err(WarpError::NotFound),
+            Ok(true) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(WarpError::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn export_account(&self, account_id: &AccountId) -> Result<AccountExport, WarpError> {
+        match self.collect_export(account_id).await {
+            Ok(export) => Ok(export),
+            Err(sqlx::Error::RowNotFound) => Err(WarpError::NotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(WarpError::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn create_account_token(&self, account_id: &AccountId, purpose: TokenPurpose, token_hash: &str, ttl_secs: u64) -> Result<(), WarpError> {
+        match sqlx::query("INSERT INTO account_tokens (account_id, purpose, token_hash, expires_on)
+                            VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))")
+            .bind(account_id.0)
+            .bind(purpose.as_str())
+            .bind(token_hash)
+            .bind(ttl_secs as f64)
+            .execute(&self.conn)
+            .await {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn use_account_token(&self, purpose: TokenPurpose, token_hash: &str) -> Result<Option<AccountId>, WarpError> {
+        match self.consume_token(purpose, token_hash).await {
+            Ok(account_id) => Ok(account_id),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(WarpError::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn set_email_verified(&self, account_id: &AccountId) -> Result<bool, WarpError> {
+        match sqlx::query("UPDATE accounts SET email_verified_on = NOW() WHERE id = $1 AND email_verified_on IS NULL")
+            .bind(account_id.0)
+            .execute(&self.conn)
+            .await {
+                Ok(res) => Ok(res.rows_affected() > 0),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn set_password(&self, account_id: &AccountId, password_hash: &str) -> Result<bool, WarpError> {
+        match sqlx::query("UPDATE accounts SET password = $1 WHERE id = $2")
+            .bind(password_hash)
+            .bind(account_id.0)
+            .execute(&self.conn)
+            .await {
+                Ok(res) if res.rows_affected() == 0 => Err(WarpError::NotFound),
+                Ok(_) => Ok(true),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    async fn set_email(&self, account_id: &AccountId, email: &str) -> Result<bool, WarpError> {
+        match sqlx::query("WITH tokens AS (UPDATE account_tokens SET used_on = NOW() WHERE account_id = $1 AND used_on IS NULL)
+                            UPDATE accounts SET email = $2, email_verified_on = NULL WHERE id = $1")
+            .bind(account_id.0)
+            .bind(email)
+            .execute(&self.conn)
+            .await {
+                Ok(res) if res.rows_affected() == 0 => Err(WarpError::NotFound),
+                Ok(_) => Ok(true),
+                Err(e) if is_unique_violation(&e) => Err(WarpError::AccountAlreadyExists),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+}
+
+#[async_trait]
+impl HealthStore for Store {
+    async fn ping(&self) -> Result<(), WarpError> {
+        match sqlx::query("SELECT 1")
+            .execute(&self.conn)
+            .await {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+
+    // a migration that failed half-way is not `success`, so it counts as pending
+    async fn pending_migrations(&self) -> Result<Vec<i64>, WarpError> {
+        match sqlx::query("SELECT version FROM _sqlx_migrations WHERE success")
+            .map(|row: PgRow| row.get("version"))
+            .fetch_all(&self.conn)
+            .await {
+                Ok(applied) => Ok(MIGRATOR.iter()
+                    .filter(|migration| !migration.migration_type.is_down_migration() && !applied.contains(&migration.version))
+                    .map(|migration| migration.version)
+                    .collect()),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(WarpError::DatabaseQueryError(e))
+                }
+            }
+    }
+}
+
+// the database may come up after the service, so failed connections are retried
+// `connect_retries` times, waiting twice as long each time up to `connect_backoff_max`;
+// a single connection instead of the pool, which would only report its own timeout
+async fn wait_for_database(config: &DatabaseConfig) -> Result<(), sqlx::Error> {
+    let mut delay = Duration::from_secs(1);
+    let mut retries = 0;
+    loop {
+        let attempt = match tokio::time::timeout(CONNECT_TIMEOUT, PgConnection::connect(&config.url)).await {
+            Ok(attempt) => attempt,
+            Err(_) => Err(sqlx::Error::Io(std::io::Error::new(std::io::ErrorKind::TimedOut, "connection attempt timed out"))),
+        };
+        match attempt {
+            Ok(connection) => return connection.close().await,
+            Err(e) if retries < config.connect_retries => {
+                retries += 1;
+                tracing::event!(tracing::Level::WARN, retry = retries, wait_secs = delay.as_secs(), "Could not connect to the database: {}", e);
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(Duration::from_secs(config.connect_backoff_max));
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// appends the filter's conditions to a query that ends in `WHERE ...`
+fn push_question_filter(query: &mut QueryBuilder<'_, Postgres>, filter: &QuestionFilter) {
+    if !filter.tags.is_empty() {
+        query.push(" AND tags @> ").push_bind(filter.tags.clone());
+    }
+    if let Some(author) = &filter.author {
+        query.push(" AND account_id = ").push_bind(author.0);
+    }
+    if let Some(created_after) = filter.created_after {
+        query.push(" AND created_on >= ").push_bind(created_after);
+    }
+    if let Some(created_before) = filter.created_before {
+        query.push(" AND created_on < ").push_bind(created_before);
+    }
+    if filter.unanswered {
+        query.push(" AND NOT EXISTS (SELECT 1 FROM answers WHERE corresponding_question = questions.id)");
+    }
+    match filter.answered {
+        Some(true) => query.push(" AND accepted_answer_id IS NOT NULL"),
+        Some(false) => query.push(" AND accepted_answer_id IS NULL"),
+        None => query,
+    };
+}
+
+fn vote_column(target: VoteTarget) -> &'static str {
+    match target {
+        VoteTarget::Question(_) => "question_id",
+        VoteTarget::Answer(_) => "answer_id",
+    }
+}
+
+fn vote_target_id(target: VoteTarget) -> i32 {
+    match target {
+        VoteTarget::Question(id) | VoteTarget::Answer(id) => id,
+    }
+}
+
+fn question_from_row(row: &PgRow) -> Question {
+    Question {
+        id: QuestionId(row.get("id")),
+        title: row.get("title"),
+        content: row.get("content"),
+        tags: row.get("tags"),
+        score: row.get("score"),
+        accepted_answer_id: row.get::<Option<i32>, _>("accepted_answer_id").map(AnswerId),
+    }
+}
+
+fn revision_from_row(row: &PgRow) -> Revision {
+    Revision {
+        question_id: QuestionId(row.get("question_id")),
+        revision: row.get("revision"),
+        title: row.get("title"),
+        content: row.get("content"),
+        tags: row.get("tags"),
+        editor_id: row.get::<Option<i32>, _>("editor_id").map(AccountId),
+        reason: row.get("reason"),
+        created_on: row.get("created_on"),
+    }
+}
+
+// snapshots the current state of a question as its next revision
+async fn record_revision(tx: &mut Transaction<'_, Postgres>, question_id: i32, note: &RevisionNote) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO question_revisions (question_id, revision, title, content, tags, editor_id, reason)
+                SELECT id, (SELECT COALESCE(MAX(revision), 0) + 1 FROM question_revisions WHERE question_id = $1),
+                    title, content, tags, $2, $3
+                FROM questions WHERE id = $1")
+        .bind(question_id)
+        .bind(note.editor_id.0)
+        .bind(note.reason.as_deref())
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+fn answer_from_row(row: &PgRow) -> Answer {
+    Answer {
+        id: AnswerId(row.get("id")),
+        content: row.get("content"),
+        question_id: QuestionId(row.get("corresponding_question")),
+        score: row.get("score"),
+    }
+}
+
+// on `accounts`, another account has the email in some case (`accounts_email_lower_idx`)
+fn is_unique_violation(error: &sqlx::Error) -> bool {
+    error.as_database_error()
+        .and_then(|e| e.code())
+        .is_some_and(|code| code == "23505")
+}
+
+// the `role` column is constrained to the known names, so this only fails if the schema and code disagree
+fn role_from_row(row: &PgRow) -> Result<Role, sqlx::Error> {
+    row.get::<String, _>("role").parse().map_err(|e: String| sqlx::Error::Decode(e.into()))
+}
+
+fn account_from_row(row: &PgRow) -> Result<Account, sqlx::Error> {
+    Ok(Account {
+        id: Some(AccountId(row.get("id"))),
+        email: row.get("email"),
+        password: row.get("password"),
+        role: role_from_row(row)?,
+        email_verified: row.get::<Option<NaiveDateTime>, _>("email_verified_on").is_some(),
+    })
+}
+
+fn account_info_from_row(row: PgRow) -> Result<AccountInfo, sqlx::Error> {
+    Ok(AccountInfo {
+        id: AccountId(row.get("id")),
+        email: row.get("email"),
+        role: role_from_row(&row)?,
+    })
+}
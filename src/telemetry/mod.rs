@@ -0,0 +1,132 @@
+// logging and tracing: the log lines filtered by `log.filter`, as text or JSON, tagged with the
+// request ID, and with the `otel` cargo feature every span exported over OTLP, continuing the
+// trace of an incoming W3C `traceparent` header and passing it on to outgoing requests
+use async_trait::async_trait;
+use reqwest_middleware::{Middleware, Next};
+use task_local_extensions::Extensions;
+use tracing::field::Empty;
+use tracing::Instrument;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::config::{LogConfig, LogFormat, OtelConfig};
+use crate::metrics;
+
+#[cfg(feature = "otel")]
+mod otlp;
+mod request_id;
+
+pub use request_id::with_request_id;
+
+/// Flushes the spans that are not exported yet when dropped; keep it until the server stopped
+#[must_use]
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    exporting: bool,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if self.exporting {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// Installs the global subscriber; has to run inside the Tokio runtime, which exports the spans
+pub fn init(log: &LogConfig, otel: &OtelConfig) -> Result<TelemetryGuard, Box<dyn std::error::Error>> {
+    let fmt = tracing_subscriber::fmt::layer().with_span_events(FmtSpan::CLOSE); // records events when each span closes
+    // one object per line, with the fields of every span the event happened in
+    let fmt = match log.format {
+        LogFormat::Text => fmt.boxed(),
+        LogFormat::Json => fmt.json().boxed(),
+    };
+    let fmt = fmt.with_filter(EnvFilter::try_new(&log.filter)?);
+
+    #[cfg(feature = "otel")]
+    let export = match otel.enabled {
+        true => Some(otlp::layer(otel)?),
+        false => None,
+    };
+    // `otel.enabled` without the feature never gets past the config validation
+    #[cfg(not(feature = "otel"))]
+    let export = otel.enabled.then(tracing_subscriber::layer::Identity::new);
+
+    let guard = TelemetryGuard {
+        #[cfg(feature = "otel")]
+        exporting: export.is_some(),
+    };
+    tracing_subscriber::registry().with(fmt).with(export).try_init()?;
+    Ok(guard)
+}
+
+/// Opens the span of an incoming request; the routes, the store and outgoing requests all add
+/// theirs beneath it
+pub fn request_span() -> warp::trace::Trace<impl Fn(warp::trace::Info<'_>) -> tracing::Span + Clone> {
+    warp::trace(|info| {
+        let route = metrics::route_label(info.path());
+        let span = tracing::info_span!(
+            "request",
+            otel.name = %format!("{} {}", info.method(), route),
+            otel.kind = "server",
+            otel.status_code = Empty,
+            http.method = %info.method(),
+            http.route = %route,
+            http.target = %info.path(),
+            http.status_code = Empty,
+            request_id = Empty,
+        );
+        #[cfg(feature = "otel")]
+        otlp::continue_trace(&span, info.request_headers());
+        span
+    })
+}
+
+// runs inside `request_span`, after the recovery, so rejected requests get their status as well
+pub fn record_status() -> warp::log::Log<impl Fn(warp::log::Info<'_>) + Clone> {
+    warp::log::custom(|info| {
+        let span = tracing::Span::current();
+        span.record("http.status_code", info.status().as_u16());
+        if info.status().is_server_error() {
+            span.record("otel.status_code", "ERROR");
+        }
+    })
+}
+
+/// Gives every attempt of an outgoing request its own span and a `traceparent` header;
+/// add it after the retry middleware
+pub struct TracePropagation;
+
+#[async_trait]
+impl Middleware for TracePropagation {
+    async fn handle(&self, req: reqwest::Request, extensions: &mut Extensions, next: Next<'_>) -> reqwest_middleware::Result<reqwest::Response> {
+        let span = tracing::info_span!(
+            "outgoing request",
+            otel.name = %format!("{} {}", req.method(), req.url().host_str().unwrap_or_default()),
+            otel.kind = "client",
+            otel.status_code = Empty,
+            http.method = %req.method(),
+            http.url = %req.url(),
+            http.status_code = Empty,
+        );
+        #[cfg(feature = "otel")]
+        let req = otlp::inject_context(&span, req);
+
+        let result = next.run(req, extensions).instrument(span.clone()).await;
+        match &result {
+            Ok(res) => {
+                span.record("http.status_code", res.status().as_u16());
+                if res.status().is_server_error() {
+                    span.record("otel.status_code", "ERROR");
+                }
+            },
+            Err(_) => {
+                span.record("otel.status_code", "ERROR");
+            },
+        }
+        result
+    }
+}
@@ -0,0 +1,49 @@
+// the parts that only exist with the `otel` cargo feature: the OTLP/HTTP exporter and the
+// W3C trace context propagation
+use opentelemetry::trace::TraceError;
+use opentelemetry::{global, KeyValue};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{self, Sampler};
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::Level;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+use warp::http::HeaderMap;
+
+use crate::config::OtelConfig;
+
+// only the spans of this crate are exported; the log filter decides what else gets printed
+pub fn layer<S>(config: &OtelConfig) -> Result<impl Layer<S>, TraceError>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().http().with_endpoint(&config.endpoint))
+        .with_trace_config(
+            trace::config()
+                .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio))))
+                .with_resource(Resource::new([KeyValue::new("service.name", config.service_name.clone())])),
+        )
+        .install_batch(runtime::Tokio)?;
+    Ok(tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(Targets::new().with_target(env!("CARGO_CRATE_NAME"), Level::INFO)))
+}
+
+// makes the span a child of the caller's span, if the request carries a valid `traceparent`
+pub fn continue_trace(span: &tracing::Span, headers: &HeaderMap) {
+    let parent = global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    span.set_parent(parent);
+}
+
+pub fn inject_context(span: &tracing::Span, mut req: reqwest::Request) -> reqwest::Request {
+    let context = span.context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut HeaderInjector(req.headers_mut())));
+    req
+}
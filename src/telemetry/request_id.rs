@@ -0,0 +1,67 @@
+// every request gets an ID: the caller's `X-Request-Id` if it sent a usable one, a new UUID
+// otherwise. It goes back in the `X-Request-Id` response header and in every problem body, and
+// is recorded on the request span, so every log line of the request carries it
+use std::convert::Infallible;
+use warp::http::HeaderValue;
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+use handle_errors::return_error; // internal library
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+// anything longer, or with other characters, is replaced instead of ending up in the logs
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+const REQUEST_ID_SYMBOLS: &str = "-_.:=;/+";
+
+#[derive(Debug, Clone)]
+pub struct RequestId(String);
+
+impl RequestId {
+    fn accept_or_generate(incoming: Option<String>) -> Self {
+        match incoming {
+            Some(id) if is_valid(&id) => Self(id),
+            _ => Self(uuid::Uuid::new_v4().to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+fn is_valid(id: &str) -> bool {
+    (1..=MAX_REQUEST_ID_LENGTH).contains(&id.len())
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || REQUEST_ID_SYMBOLS.contains(c))
+}
+
+// has to run inside `request_span`, which gets the ID recorded
+fn request_id() -> impl Filter<Extract = (RequestId,), Error = Infallible> + Clone {
+    // a header that isn't valid UTF-8 counts as missing
+    warp::header::optional::<String>(REQUEST_ID_HEADER)
+        .or(warp::any().map(|| None))
+        .unify()
+        .map(|incoming: Option<String>| {
+            let id = RequestId::accept_or_generate(incoming);
+            tracing::Span::current().record("request_id", id.as_str());
+            id
+        })
+}
+
+/// Runs `routes` under a request ID and turns their rejections into problem responses carrying it
+pub fn with_request_id<F, R>(routes: F) -> impl Filter<Extract = (Response,), Error = Infallible> + Clone
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    // the rejection becomes a value, so it can be answered together with the ID
+    let outcome = routes
+        .map(|reply: R| Ok(reply.into_response()))
+        .or_else(|rejection| async move { Ok::<_, Infallible>((Err(rejection),)) });
+
+    request_id().and(outcome).map(|id: RequestId, outcome: Result<Response, Rejection>| {
+        let mut response = outcome.unwrap_or_else(|rejection| return_error(&rejection, id.as_str()));
+        if let Ok(value) = HeaderValue::from_str(id.as_str()) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        response
+    })
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 use chrono::prelude::*;
+use crate::types::answer::Answer;
+use crate::types::question::Question;
+use crate::types::vote::CastVote;
 
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -7,23 +10,186 @@ use chrono::prelude::*;
     pub id: Option<AccountId>,
     pub email: String,
     pub password: String,
+    // never taken from a request body; only admins can change it
+    #[serde(skip)]
+    pub role: Role,
+    // set through the link in the verification email
+    #[serde(skip)]
+    pub email_verified: bool,
  }
 
  #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
  pub struct AccountId(pub i32);
 
+ // ordered by privilege: every role may do everything the roles before it may
+ #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+ #[serde(rename_all = "lowercase")]
+ pub enum Role {
+   #[default]
+   User,
+   // may edit and delete any question or answer
+   Moderator,
+   // may additionally manage accounts
+   Admin,
+ }
+
+ impl Role {
+   pub fn as_str(&self) -> &'static str {
+      match self {
+         Role::User => "user",
+         Role::Moderator => "moderator",
+         Role::Admin => "admin",
+      }
+   }
+
+   pub fn can_moderate(&self) -> bool {
+      *self >= Role::Moderator
+   }
+ }
+
+ impl std::str::FromStr for Role {
+   type Err = String;
+
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      match s {
+         "user" => Ok(Role::User),
+         "moderator" => Ok(Role::Moderator),
+         "admin" => Ok(Role::Admin),
+         _ => Err(format!("unknown role `{}`", s)),
+      }
+   }
+ }
+
+ // what admins get to see of an account; never includes the password hash
+ #[derive(Debug, Serialize, Deserialize, Clone)]
+ pub struct AccountInfo {
+   pub id: AccountId,
+   pub email: String,
+   pub role: Role,
+ }
+
+ #[derive(Debug, Serialize, Deserialize, Clone)]
+ pub struct RoleUpdate {
+   pub role: Role,
+ }
+
  #[derive(Debug, Serialize, Deserialize, Clone)]
  pub struct NewAccount {
     pub email: String,
     pub password: String,
  }
 
+ #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+ pub struct SessionId(pub i32);
+
+ // claims carried by an access token
  #[derive(Debug, Serialize, Deserialize, Clone)]
  pub struct Session {
    // session expiration date
    pub exp: DateTime<Utc>,
    // account_id of current session
    pub account_id: AccountId,
+   // server-side login session the token belongs to; revoking it invalidates the token
+   pub session_id: SessionId,
+   // role of the account when the token was issued; tokens issued before roles existed are plain users
+   #[serde(default)]
+   pub role: Role,
    // start date of the session, nbf = not before
    pub nbf: DateTime<Utc>
- }
\ No newline at end of file
+ }
+
+ // returned by `/login` and `/refresh`
+ #[derive(Debug, Serialize, Deserialize, Clone)]
+ pub struct TokenPair {
+   pub access_token: String,
+   // seconds until the access token expires
+   pub expires_in: u64,
+   // single use; every `/refresh` returns a new one
+   pub refresh_token: String,
+ }
+
+ #[derive(Debug, Serialize, Deserialize, Clone)]
+ pub struct RefreshRequest {
+   pub refresh_token: String,
+ }
+
+ // what a token sent by email allows; every token works for exactly one purpose, once
+ #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+ pub enum TokenPurpose {
+   VerifyEmail,
+   ResetPassword,
+ }
+
+ impl TokenPurpose {
+   pub fn as_str(&self) -> &'static str {
+      match self {
+         TokenPurpose::VerifyEmail => "verify_email",
+         TokenPurpose::ResetPassword => "reset_password",
+      }
+   }
+ }
+
+ // `POST /email/verify`
+ #[derive(Debug, Serialize, Deserialize, Clone)]
+ pub struct VerifyEmail {
+   pub token: String,
+ }
+
+ // `POST /email/resend` and `POST /password/forgot`
+ #[derive(Debug, Serialize, Deserialize, Clone)]
+ pub struct EmailRequest {
+   pub email: String,
+ }
+
+ // `POST /password/reset`
+ #[derive(Debug, Serialize, Deserialize, Clone)]
+ pub struct PasswordReset {
+   pub token: String,
+   pub password: String,
+ }
+
+ // `PUT /account/password`
+ #[derive(Debug, Serialize, Deserialize, Clone)]
+ pub struct PasswordChange {
+   pub current_password: String,
+   pub new_password: String,
+ }
+
+ // `PUT /account/email`; the new address has to be verified again
+ #[derive(Debug, Serialize, Deserialize, Clone)]
+ pub struct EmailChange {
+   pub email: String,
+   pub password: String,
+ }
+
+ // what happens to the questions and answers of a deleted account
+ #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+ #[serde(rename_all = "lowercase")]
+ pub enum AccountContent {
+   // kept without an owner, so threads stay readable
+   #[default]
+   Anonymize,
+   // removed together with the answers of others to the account's questions
+   Delete,
+ }
+
+ // `DELETE /account`
+ #[derive(Debug, Serialize, Deserialize, Clone)]
+ pub struct AccountDeletion {
+   pub password: String,
+   #[serde(default)]
+   pub content: AccountContent,
+ }
+
+ // `GET /account/export`: everything stored about an account
+ #[derive(Debug, Serialize, Clone)]
+ pub struct AccountExport {
+   pub account: AccountInfo,
+   pub email_verified: bool,
+   pub questions: Vec<Question>,
+   // in the trash, until they are restored or purged
+   pub deleted_questions: Vec<Question>,
+   pub answers: Vec<Answer>,
+   pub votes: Vec<CastVote>,
+   pub exported_on: DateTime<Utc>,
+ }
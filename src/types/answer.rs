@@ -9,11 +9,20 @@ pub struct AnswerId(pub i32);
 pub struct Answer {
     pub id: AnswerId,
     pub content: String,
-    pub question_id: QuestionId
+    pub question_id: QuestionId,
+    // sum of all up (+1) and down (-1) votes
+    #[serde(default)]
+    pub score: i64,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct NewAnswer {
     pub content: String,
     pub question_id: QuestionId
-}
\ No newline at end of file
+}
+
+// body of `PUT /answers/{id}`; an answer cannot move to another question
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UpdateAnswer {
+    pub content: String
+}
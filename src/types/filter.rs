@@ -0,0 +1,216 @@
+use std::collections::btree_map::{BTreeMap, Entry};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use handle_errors::{FieldError, WarpError}; // internal library
+
+use crate::types::account::AccountId;
+use crate::types::pagination::{self, Page, Pagination};
+
+// parameters handed on to `pagination::get_page`
+const PAGE_PARAMS: [&str; 3] = ["limit", "offset", "cursor"];
+
+/// Which questions `GET /questions` returns, and in which order
+/// `/questions?tag=rust&tag=async&author=3&created_after=2023-10-01&answered=false&sort=newest`
+#[derive(Debug, Clone, Default)]
+pub struct QuestionFilter {
+    // questions carrying every one of these tags
+    pub tags: Vec<String>,
+    pub author: Option<AccountId>,
+    // inclusive
+    pub created_after: Option<NaiveDateTime>,
+    // exclusive, so consecutive ranges don't overlap
+    pub created_before: Option<NaiveDateTime>,
+    // only questions without any answer
+    pub unanswered: bool,
+    // `Some(true)`: only questions with an accepted answer, `Some(false)`: only those without
+    pub answered: Option<bool>,
+    pub sort: QuestionSort,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuestionSort {
+    // the order questions were listed in before sorting existed
+    #[default]
+    Oldest,
+    Newest,
+    // offset pagination only, like `Votes`; counts are not a stable cursor position
+    MostAnswers,
+    // highest score first
+    Votes,
+}
+
+impl QuestionSort {
+    // whether keyset pagination can walk this order
+    pub fn supports_cursor(&self) -> bool {
+        matches!(self, QuestionSort::Oldest | QuestionSort::Newest)
+    }
+}
+
+/// Everything `GET /questions` understands, parsed from the query string
+#[derive(Debug)]
+pub struct QuestionQuery {
+    pub filter: QuestionFilter,
+    pub page: Page,
+}
+
+/// Parses the raw query string of `GET /questions`, reporting every invalid parameter at once.
+/// `tag` may be repeated, every other parameter must appear at most once
+pub fn get_question_query(raw: &str) -> Result<QuestionQuery, WarpError> {
+    let pairs: Vec<(String, String)> = serde_urlencoded::from_str(raw)
+        .map_err(|e| WarpError::ValidationError(vec![FieldError::new("query", e.to_string())]))?;
+
+    let mut errors = Vec::new();
+    let mut filter = QuestionFilter::default();
+    // sorted, so errors come back in a stable order
+    let mut params: BTreeMap<String, String> = BTreeMap::new();
+    for (key, value) in pairs {
+        if key == "tag" {
+            match value.trim() {
+                "" => errors.push(FieldError::new("tag", "must not be empty")),
+                tag => filter.tags.push(tag.to_owned()),
+            }
+            continue;
+        }
+        match params.entry(key) {
+            Entry::Occupied(entry) => errors.push(FieldError::new(entry.key(), "must not be given more than once")),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            },
+        }
+    }
+
+    for (key, value) in &params {
+        match key.as_str() {
+            "author" => match value.parse::<i32>() {
+                Ok(author) => filter.author = Some(AccountId(author)),
+                Err(e) => errors.push(FieldError::new(key, format!("must be an account ID: {}", e))),
+            },
+            "created_after" => match parse_timestamp(value) {
+                Some(timestamp) => filter.created_after = Some(timestamp),
+                None => errors.push(FieldError::new(key, "must be a date (2023-10-01) or an RFC 3339 timestamp")),
+            },
+            "created_before" => match parse_timestamp(value) {
+                Some(timestamp) => filter.created_before = Some(timestamp),
+                None => errors.push(FieldError::new(key, "must be a date (2023-10-01) or an RFC 3339 timestamp")),
+            },
+            "unanswered" => match parse_bool(value) {
+                Some(unanswered) => filter.unanswered = unanswered,
+                None => errors.push(FieldError::new(key, "must be `true` or `false`")),
+            },
+            "answered" => match parse_bool(value) {
+                Some(answered) => filter.answered = Some(answered),
+                None => errors.push(FieldError::new(key, "must be `true` or `false`")),
+            },
+            "sort" => match value.as_str() {
+                "oldest" => filter.sort = QuestionSort::Oldest,
+                "newest" => filter.sort = QuestionSort::Newest,
+                "most_answers" => filter.sort = QuestionSort::MostAnswers,
+                "votes" => filter.sort = QuestionSort::Votes,
+                _ => errors.push(FieldError::new(key, "must be one of `newest`, `oldest`, `most_answers`, `votes`")),
+            },
+            key if PAGE_PARAMS.contains(&key) => (),
+            _ => errors.push(FieldError::new(key, "is not a known parameter")),
+        }
+    }
+
+    if let (Some(after), Some(before)) = (filter.created_after, filter.created_before) {
+        if after >= before {
+            errors.push(FieldError::new("created_before", "must be later than created_after"));
+        }
+    }
+
+    // without any pagination parameters every matching question is returned, like before
+    params.retain(|key, _| PAGE_PARAMS.contains(&key.as_str()));
+    let page = if params.is_empty() {
+        Page::Offset(Pagination::default())
+    } else {
+        match pagination::get_page(params.into_iter().collect()) {
+            Ok(page) => page,
+            Err(WarpError::ValidationError(field_errors)) => {
+                errors.extend(field_errors);
+                Page::Offset(Pagination::default())
+            },
+            Err(e) => return Err(e),
+        }
+    };
+    if matches!(page, Page::Cursor(_)) && !filter.sort.supports_cursor() {
+        errors.push(FieldError::new("sort", "`most_answers` and `votes` can only be paged with limit and offset"));
+    }
+
+    if !errors.is_empty() {
+        return Err(WarpError::ValidationError(errors));
+    }
+    Ok(QuestionQuery { filter, page })
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+// `2023-10-01` means midnight UTC; full timestamps may carry any offset
+fn parse_timestamp(value: &str) -> Option<NaiveDateTime> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Some(timestamp.naive_utc());
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_fields(raw: &str) -> Vec<String> {
+        match get_question_query(raw) {
+            Err(WarpError::ValidationError(errors)) => errors.into_iter().map(|error| error.field).collect(),
+            other => panic!("expected a validation error for {:?}, got {:?}", raw, other),
+        }
+    }
+
+    #[test]
+    fn empty_query_lists_everything_oldest_first() {
+        let query = get_question_query("").unwrap();
+        assert!(query.filter.tags.is_empty());
+        assert_eq!(query.filter.sort, QuestionSort::Oldest);
+        assert!(matches!(query.page, Page::Offset(Pagination { limit: None, offset: 0 })));
+    }
+
+    #[test]
+    fn parses_every_filter() {
+        let query = get_question_query(
+            "tag=rust&tag=async&author=3&created_after=2023-10-01&created_before=2023-11-01T12:00:00%2B02:00&answered=false&unanswered=1&sort=newest"
+        ).unwrap();
+        let filter = query.filter;
+        assert_eq!(filter.tags, vec!["rust", "async"]);
+        assert_eq!(filter.author, Some(AccountId(3)));
+        assert_eq!(filter.created_after, NaiveDate::from_ymd_opt(2023, 10, 1).unwrap().and_hms_opt(0, 0, 0));
+        assert_eq!(filter.created_before, NaiveDate::from_ymd_opt(2023, 11, 1).unwrap().and_hms_opt(10, 0, 0));
+        assert_eq!(filter.answered, Some(false));
+        assert!(filter.unanswered);
+        assert_eq!(filter.sort, QuestionSort::Newest);
+    }
+
+    #[test]
+    fn reports_every_invalid_parameter_in_order() {
+        assert_eq!(
+            invalid_fields("tag=&author=me&answered=maybe&sort=random&colour=red"),
+            vec!["tag", "answered", "author", "colour", "sort"],
+        );
+    }
+
+    #[test]
+    fn rejects_repeated_parameters_and_empty_ranges() {
+        assert_eq!(invalid_fields("sort=newest&sort=oldest"), vec!["sort"]);
+        assert_eq!(invalid_fields("created_after=2023-10-01&created_before=2023-10-01"), vec!["created_before"]);
+    }
+
+    #[test]
+    fn cursor_paging_only_for_stable_orders() {
+        assert!(matches!(get_question_query("limit=10&sort=newest").unwrap().page, Page::Cursor(_)));
+        assert!(matches!(get_question_query("limit=10&offset=0&sort=votes").unwrap().page, Page::Offset(_)));
+        assert_eq!(invalid_fields("limit=10&sort=votes"), vec!["sort"]);
+        assert_eq!(invalid_fields("limit=0&cursor=nonsense"), vec!["limit", "cursor"]);
+    }
+}
@@ -1,4 +1,8 @@
 pub mod question;
 pub mod answer;
 pub mod pagination;
-pub mod account;
\ No newline at end of file
+pub mod filter;
+pub mod account;
+pub mod search;
+pub mod vote;
+pub mod revision;
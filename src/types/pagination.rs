@@ -1,5 +1,11 @@
 use std::collections::HashMap;
-use handle_errors::WarpError; // internal library
+use chrono::NaiveDateTime;
+use serde::{Serialize, Serializer};
+use handle_errors::{FieldError, WarpError}; // internal library
+
+// page size in cursor mode when the client does not ask for a `limit`
+pub const DEFAULT_PAGE_SIZE: i32 = 20;
+pub const MAX_PAGE_SIZE: i32 = 100;
 
 // to capture the values of the *start & *end values in the URL
 // Pagination number range that is being extracted from query params
@@ -24,11 +30,258 @@ pub struct Pagination {
 /// assert_eq!(p.start, Some(1));
 /// assert_eq!(p.end, 10);
 pub fn get_pagination(params: HashMap<String, String>) -> Result<Pagination, WarpError> {
-    if params.contains_key("limit") && params.contains_key("offset") {
-        return Ok(Pagination {
-            limit: Some(params.get("limit").unwrap().parse::<i32>().map_err(WarpError::ParseError)?),
-            offset: params.get("offset").unwrap().parse::<i32>().map_err(WarpError::ParseError)?
+    // report every bad or missing field at once instead of stopping at the first one
+    match (parse_field(&params, "limit"), parse_field(&params, "offset")) {
+        (Ok(limit), Ok(offset)) => Ok(Pagination { limit: Some(limit), offset }),
+        (limit, offset) => Err(WarpError::ValidationError(
+            [limit.err(), offset.err()].into_iter().flatten().collect()
+        )),
+    }
+}
+
+fn parse_field(params: &HashMap<String, String>, field: &str) -> Result<i32, FieldError> {
+    match params.get(field).map(|value| value.parse::<i32>()) {
+        Some(Ok(value)) if value >= 0 => Ok(value),
+        Some(Ok(_)) => Err(FieldError::new(field, "must not be negative")),
+        Some(Err(e)) => Err(FieldError::new(field, format!("must be an integer: {}", e))),
+        None => Err(FieldError::new(field, "is required")),
+    }
+}
+
+/// How a listing is paged: the old `limit`/`offset` mode, or keyset pagination with cursors
+#[derive(Debug)]
+pub enum Page {
+    Offset(Pagination),
+    Cursor(KeysetPagination),
+}
+
+#[derive(Debug)]
+pub struct KeysetPagination {
+    pub limit: i32,
+    // `None` for the first page
+    pub cursor: Option<Cursor>,
+}
+
+/// Position in a listing ordered by `(created_on, id)`; clients only ever see it encoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_on: NaiveDateTime,
+    pub id: i32,
+    pub direction: Direction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    // the page of items after the cursor position
+    After,
+    // the page of items before it, for `prev_cursor`
+    Before,
+}
+
+impl Cursor {
+    // `<microseconds since epoch>.<id>.<a|b>`, base64url encoded so clients don't build their own
+    pub fn encode(&self) -> String {
+        let direction = match self.direction {
+            Direction::After => "a",
+            Direction::Before => "b",
+        };
+        let raw = format!("{}.{}.{}", self.created_on.and_utc().timestamp_micros(), self.id, direction);
+        base64::encode_config(raw, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(encoded: &str) -> Option<Self> {
+        let raw = String::from_utf8(base64::decode_config(encoded, base64::URL_SAFE_NO_PAD).ok()?).ok()?;
+        let mut parts = raw.split('.');
+        let created_on = chrono::DateTime::from_timestamp_micros(parts.next()?.parse().ok()?)?.naive_utc();
+        let id = parts.next()?.parse().ok()?;
+        let direction = match parts.next()? {
+            "a" => Direction::After,
+            "b" => Direction::Before,
+            _ => return None,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self { created_on, id, direction })
+    }
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.encode())
+    }
+}
+
+/// One page of a cursor-paginated listing
+#[derive(Debug, Serialize)]
+pub struct KeysetPage<T> {
+    pub items: Vec<T>,
+    // absent on the last page
+    pub next_cursor: Option<Cursor>,
+    // absent on the first page
+    pub prev_cursor: Option<Cursor>,
+}
+
+impl<T> KeysetPage<T> {
+    /// Builds the page from up to `limit + 1` rows as `(item, created_on, id)`, in query order:
+    /// ascending for the first page and `Direction::After`, descending for `Direction::Before`.
+    /// The extra row only tells whether there is another page in that direction
+    pub fn from_rows(mut rows: Vec<(T, NaiveDateTime, i32)>, page: &KeysetPagination) -> Self {
+        let has_more = rows.len() > page.limit as usize;
+        rows.truncate(page.limit as usize);
+        let direction = page.cursor.as_ref().map(|cursor| cursor.direction);
+        if direction == Some(Direction::Before) {
+            rows.reverse();
+        }
+
+        let cursor_at = |row: Option<&(T, NaiveDateTime, i32)>, direction| row.map(|(_, created_on, id)| Cursor {
+            created_on: *created_on,
+            id: *id,
+            direction,
         });
+        // on an empty page, point back to where the client came from
+        let came_from = |direction| page.cursor.as_ref().map(|cursor| Cursor { direction, ..cursor.clone() });
+
+        let (next_cursor, prev_cursor) = match direction {
+            None => (cursor_at(rows.last(), Direction::After).filter(|_| has_more), None),
+            Some(Direction::After) => (
+                cursor_at(rows.last(), Direction::After).filter(|_| has_more),
+                cursor_at(rows.first(), Direction::Before).or_else(|| came_from(Direction::Before)),
+            ),
+            Some(Direction::Before) => (
+                cursor_at(rows.last(), Direction::After).or_else(|| came_from(Direction::After)),
+                cursor_at(rows.first(), Direction::Before).filter(|_| has_more),
+            ),
+        };
+
+        Self {
+            items: rows.into_iter().map(|(item, _, _)| item).collect(),
+            next_cursor,
+            prev_cursor,
+        }
     }
-    Err(WarpError::MissingParameters)
-}
\ No newline at end of file
+}
+
+/// Picks the pagination mode from the query parameters:
+/// `?limit=10&offset=20` pages by offset like before, `?limit=10` and
+/// `?limit=10&cursor=...` page by cursor
+pub fn get_page(params: HashMap<String, String>) -> Result<Page, WarpError> {
+    if params.contains_key("offset") {
+        if params.contains_key("cursor") {
+            return Err(WarpError::ValidationError(vec![
+                FieldError::new("cursor", "cannot be combined with offset"),
+            ]));
+        }
+        return get_pagination(params).map(Page::Offset);
+    }
+
+    let mut errors = Vec::new();
+    let limit = match params.get("limit").map(|_| parse_field(&params, "limit")) {
+        Some(Ok(limit)) if (1..=MAX_PAGE_SIZE).contains(&limit) => limit,
+        Some(Ok(_)) => {
+            errors.push(FieldError::new("limit", format!("must be between 1 and {}", MAX_PAGE_SIZE)));
+            DEFAULT_PAGE_SIZE
+        },
+        Some(Err(e)) => {
+            errors.push(e);
+            DEFAULT_PAGE_SIZE
+        },
+        None => DEFAULT_PAGE_SIZE,
+    };
+    let cursor = match params.get("cursor").map(|cursor| Cursor::decode(cursor)) {
+        Some(Some(cursor)) => Some(cursor),
+        Some(None) => {
+            errors.push(FieldError::new("cursor", "is not a cursor returned by this API"));
+            None
+        },
+        None => None,
+    };
+
+    if !errors.is_empty() {
+        return Err(WarpError::ValidationError(errors));
+    }
+    Ok(Page::Cursor(KeysetPagination { limit, cursor }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> NaiveDateTime {
+        chrono::DateTime::from_timestamp(1_700_000_000 + seconds, 123_456_000).unwrap().naive_utc()
+    }
+
+    fn rows(ids: &[i32]) -> Vec<(i32, NaiveDateTime, i32)> {
+        ids.iter().map(|&id| (id, at(id as i64), id)).collect()
+    }
+
+    fn cursor(id: i32, direction: Direction) -> Cursor {
+        Cursor { created_on: at(id as i64), id, direction }
+    }
+
+    fn page(limit: i32, cursor: Option<Cursor>) -> KeysetPagination {
+        KeysetPagination { limit, cursor }
+    }
+
+    #[test]
+    fn cursor_survives_encoding() {
+        for direction in [Direction::After, Direction::Before] {
+            let original = cursor(42, direction);
+            assert_eq!(Cursor::decode(&original.encode()), Some(original));
+        }
+    }
+
+    #[test]
+    fn foreign_cursors_are_rejected() {
+        let encode = |raw: &str| base64::encode_config(raw, base64::URL_SAFE_NO_PAD);
+        assert_eq!(Cursor::decode("not base64!"), None);
+        assert_eq!(Cursor::decode(&encode("1700000000.1")), None);
+        assert_eq!(Cursor::decode(&encode("1700000000.1.x")), None);
+        assert_eq!(Cursor::decode(&encode("1700000000.1.a.extra")), None);
+        assert_eq!(Cursor::decode(&encode("yesterday.1.a")), None);
+    }
+
+    #[test]
+    fn first_page_points_forward_only() {
+        let page = KeysetPage::from_rows(rows(&[1, 2, 3]), &page(2, None));
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor, Some(cursor(2, Direction::After)));
+        assert_eq!(page.prev_cursor, None);
+    }
+
+    #[test]
+    fn last_page_has_no_next_cursor() {
+        let page = KeysetPage::from_rows(rows(&[3, 4]), &page(2, Some(cursor(2, Direction::After))));
+        assert_eq!(page.items, vec![3, 4]);
+        assert_eq!(page.next_cursor, None);
+        assert_eq!(page.prev_cursor, Some(cursor(3, Direction::Before)));
+    }
+
+    #[test]
+    fn backwards_page_comes_in_descending_order() {
+        let page = KeysetPage::from_rows(rows(&[4, 3, 2]), &page(2, Some(cursor(5, Direction::Before))));
+        assert_eq!(page.items, vec![3, 4]);
+        assert_eq!(page.next_cursor, Some(cursor(4, Direction::After)));
+        assert_eq!(page.prev_cursor, Some(cursor(3, Direction::Before)));
+    }
+
+    #[test]
+    fn empty_page_points_back() {
+        let page = KeysetPage::<i32>::from_rows(Vec::new(), &page(2, Some(cursor(7, Direction::After))));
+        assert!(page.items.is_empty());
+        assert_eq!(page.next_cursor, None);
+        assert_eq!(page.prev_cursor, Some(cursor(7, Direction::Before)));
+    }
+
+    #[test]
+    fn offset_cannot_be_combined_with_cursor() {
+        let params = HashMap::from([
+            ("offset".to_owned(), "0".to_owned()),
+            ("cursor".to_owned(), cursor(1, Direction::After).encode()),
+        ]);
+        match get_page(params) {
+            Err(WarpError::ValidationError(errors)) => assert_eq!(errors[0].field, "cursor"),
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+}
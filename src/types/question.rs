@@ -1,4 +1,5 @@
 use serde::{Serialize,Deserialize};
+use crate::types::answer::{Answer, AnswerId};
 
 
 // database creation structure
@@ -16,6 +17,12 @@ pub struct Question {
     pub title: String,
     pub content: String,
     pub tags: Option<Vec<String>>,
+    // sum of all up (+1) and down (-1) votes; ignored in request bodies
+    #[serde(default)]
+    pub score: i64,
+    // set by the question's owner through `/questions/{id}/accept/{answer_id}`; ignored in request bodies
+    #[serde(default)]
+    pub accepted_answer_id: Option<AnswerId>,
 }
 
 #[derive(Debug, Serialize, Eq, Hash, Clone, PartialEq, Deserialize)]
@@ -27,4 +34,22 @@ pub struct NewQuestion {
     pub title: String,
     pub content: String,
     pub tags: Option<Vec<String>>
-}
\ No newline at end of file
+}
+
+// body of `PUT /questions/{id}`; `reason` ends up in the revision history
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateQuestion {
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+// `GET /questions/{id}`: the question with all of its answers
+#[derive(Debug, Serialize, Clone)]
+pub struct QuestionWithAnswers {
+    #[serde(flatten)]
+    pub question: Question,
+    pub answers: Vec<Answer>,
+}
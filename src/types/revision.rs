@@ -0,0 +1,149 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+
+use crate::types::account::AccountId;
+use crate::types::question::QuestionId;
+
+// one version of a question; revision 1 is the question as first posted
+#[derive(Debug, Serialize, Clone)]
+pub struct Revision {
+    pub question_id: QuestionId,
+    pub revision: i32,
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    // `None` for questions seeded without an owner
+    pub editor_id: Option<AccountId>,
+    pub reason: Option<String>,
+    pub created_on: NaiveDateTime,
+}
+
+// who made an edit and why; stored with the revision it creates
+#[derive(Debug, Clone)]
+pub struct RevisionNote {
+    pub editor_id: AccountId,
+    pub reason: Option<String>,
+}
+
+// optional body of `POST /questions/{id}/revisions/{rev}/rollback`
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RollbackRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineChange {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DiffLine {
+    pub change: LineChange,
+    pub line: String,
+}
+
+/// `GET /questions/{id}/revisions/{rev}/diff`: what changed from revision `from` to revision `to`
+#[derive(Debug, Serialize, Clone)]
+pub struct RevisionDiff {
+    pub from: i32,
+    pub to: i32,
+    pub title: Vec<DiffLine>,
+    pub content: Vec<DiffLine>,
+    // one tag per line
+    pub tags: Vec<DiffLine>,
+}
+
+impl RevisionDiff {
+    pub fn between(old: &Revision, new: &Revision) -> Self {
+        let tags = |revision: &Revision| revision.tags.as_deref().unwrap_or_default().join("\n");
+        Self {
+            from: old.revision,
+            to: new.revision,
+            title: diff_lines(&old.title, &new.title),
+            content: diff_lines(&old.content, &new.content),
+            tags: diff_lines(&tags(old), &tags(new)),
+        }
+    }
+}
+
+fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    // otherwise an unchanged last line counts as changed once a line is appended after it
+    let (old, new) = (with_final_newline(old), with_final_newline(new));
+    TextDiff::from_lines(&old, &new)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            change: match change.tag() {
+                ChangeTag::Equal => LineChange::Equal,
+                ChangeTag::Insert => LineChange::Insert,
+                ChangeTag::Delete => LineChange::Delete,
+            },
+            // lines are reported without their line break
+            line: change.value().trim_end_matches(['\r', '\n']).to_owned(),
+        })
+        .collect()
+}
+
+fn with_final_newline(text: &str) -> String {
+    match text.is_empty() || text.ends_with('\n') {
+        true => text.to_owned(),
+        false => format!("{}\n", text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn revision(revision: i32, title: &str, content: &str, tags: Option<&[&str]>) -> Revision {
+        Revision {
+            question_id: QuestionId(1),
+            revision,
+            title: title.to_owned(),
+            content: content.to_owned(),
+            tags: tags.map(|tags| tags.iter().map(|tag| tag.to_string()).collect()),
+            editor_id: None,
+            reason: None,
+            created_on: NaiveDateTime::default(),
+        }
+    }
+
+    fn changes(lines: &[DiffLine]) -> Vec<(LineChange, &str)> {
+        lines.iter().map(|line| (line.change, line.line.as_str())).collect()
+    }
+
+    #[test]
+    fn unchanged_revisions_only_have_equal_lines() {
+        let old = revision(1, "Title", "first\nsecond", Some(&["rust"]));
+        let diff = RevisionDiff::between(&old, &revision(2, "Title", "first\nsecond", Some(&["rust"])));
+        assert_eq!((diff.from, diff.to), (1, 2));
+        assert!(diff.title.iter().chain(&diff.content).chain(&diff.tags).all(|line| line.change == LineChange::Equal));
+    }
+
+    #[test]
+    fn appending_a_line_keeps_the_last_one_equal() {
+        let diff = RevisionDiff::between(
+            &revision(1, "Title", "first\nsecond", None),
+            &revision(2, "Title", "first\nsecond\nthird", None),
+        );
+        assert_eq!(changes(&diff.content), vec![
+            (LineChange::Equal, "first"),
+            (LineChange::Equal, "second"),
+            (LineChange::Insert, "third"),
+        ]);
+    }
+
+    #[test]
+    fn changed_lines_are_deleted_and_inserted() {
+        let diff = RevisionDiff::between(
+            &revision(1, "Old title", "", None),
+            &revision(3, "New title", "", Some(&["rust", "warp"])),
+        );
+        assert_eq!(changes(&diff.title), vec![(LineChange::Delete, "Old title"), (LineChange::Insert, "New title")]);
+        assert!(diff.content.is_empty());
+        assert_eq!(changes(&diff.tags), vec![(LineChange::Insert, "rust"), (LineChange::Insert, "warp")]);
+    }
+}
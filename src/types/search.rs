@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use handle_errors::{FieldError, WarpError}; // internal library
+
+use crate::types::account::AccountId;
+use crate::types::answer::AnswerId;
+use crate::types::pagination;
+use crate::types::question::QuestionId;
+
+// hits per page when the client does not ask for a `limit`
+const DEFAULT_LIMIT: i32 = 20;
+
+/// A parsed `GET /search` request
+/// `/search?q=borrow+checker&tag=rust&author=3&limit=10&offset=0`
+#[derive(Debug, Clone)]
+pub struct Search {
+    // free text in web search syntax: `"exact phrase"`, `-excluded`, `this or that`
+    pub query: String,
+    // only hits on questions with this tag (for answers: the tag of their question)
+    pub tag: Option<String>,
+    // only questions and answers written by this account
+    pub author: Option<AccountId>,
+    pub limit: i32,
+    pub offset: i32,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HitKind {
+    Question,
+    Answer,
+}
+
+// a question or answer matching the search, best match first
+#[derive(Debug, Serialize, Clone)]
+pub struct SearchHit {
+    pub kind: HitKind,
+    pub question_id: QuestionId,
+    // only set for answers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub answer_id: Option<AnswerId>,
+    // title of the question, also for answers, so clients can render a result list
+    pub title: String,
+    // matching excerpt as HTML: the text is escaped and matched words are wrapped in `<mark>` and `</mark>`
+    pub snippet: String,
+    pub rank: f32,
+}
+
+/// Validates the query parameters of `/search`, reporting every invalid field at once
+pub fn get_search(params: HashMap<String, String>) -> Result<Search, WarpError> {
+    let mut errors = Vec::new();
+
+    let query = match params.get("q").map(|q| q.trim()) {
+        Some(q) if !q.is_empty() => q.to_owned(),
+        Some(_) => {
+            errors.push(FieldError::new("q", "must not be empty"));
+            String::new()
+        },
+        None => {
+            errors.push(FieldError::new("q", "is required"));
+            String::new()
+        },
+    };
+
+    let tag = params.get("tag").map(|tag| tag.trim().to_owned()).filter(|tag| !tag.is_empty());
+
+    let author = match params.get("author").map(|author| author.parse::<i32>()) {
+        Some(Ok(author)) => Some(AccountId(author)),
+        Some(Err(e)) => {
+            errors.push(FieldError::new("author", format!("must be an account ID: {}", e)));
+            None
+        },
+        None => None,
+    };
+
+    // pagination is optional here, but if used it follows the same rules as everywhere else
+    let mut page = pagination::Pagination { limit: Some(DEFAULT_LIMIT), offset: 0 };
+    if params.contains_key("limit") || params.contains_key("offset") {
+        match pagination::get_pagination(params) {
+            Ok(pagination) => page = pagination,
+            Err(WarpError::ValidationError(field_errors)) => errors.extend(field_errors),
+            Err(e) => return Err(e),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(WarpError::ValidationError(errors));
+    }
+    Ok(Search {
+        query,
+        tag,
+        author,
+        limit: page.limit.unwrap_or(DEFAULT_LIMIT),
+        offset: page.offset,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn defaults_to_the_first_page() {
+        let search = get_search(params(&[("q", "  borrow checker "), ("tag", " ")])).unwrap();
+        assert_eq!(search.query, "borrow checker");
+        assert_eq!(search.tag, None);
+        assert_eq!(search.author, None);
+        assert_eq!((search.limit, search.offset), (DEFAULT_LIMIT, 0));
+    }
+
+    #[test]
+    fn takes_filters_and_pagination() {
+        let search = get_search(params(&[("q", "lifetimes"), ("tag", "rust"), ("author", "3"), ("limit", "5"), ("offset", "10")])).unwrap();
+        assert_eq!(search.tag.as_deref(), Some("rust"));
+        assert_eq!(search.author, Some(AccountId(3)));
+        assert_eq!((search.limit, search.offset), (5, 10));
+    }
+
+    #[test]
+    fn reports_every_invalid_field() {
+        match get_search(params(&[("q", " "), ("author", "me"), ("limit", "-1")])) {
+            Err(WarpError::ValidationError(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+                assert_eq!(fields, vec!["q", "author", "limit", "offset"]);
+            },
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        assert!(matches!(get_search(HashMap::new()), Err(WarpError::ValidationError(_))));
+    }
+}
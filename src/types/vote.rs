@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use crate::types::answer::AnswerId;
+use crate::types::question::QuestionId;
+
+// what a vote is cast on; every account has at most one vote per target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VoteTarget {
+    Question(i32),
+    Answer(i32),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VoteDirection {
+    Up,
+    Down,
+}
+
+impl VoteDirection {
+    // what the vote adds to the score
+    pub fn value(&self) -> i16 {
+        match self {
+            VoteDirection::Up => 1,
+            VoteDirection::Down => -1,
+        }
+    }
+
+    // the sign of a stored vote
+    pub fn from_value(value: i16) -> Self {
+        if value > 0 { VoteDirection::Up } else { VoteDirection::Down }
+    }
+}
+
+// body of `POST /questions/{id}/vote` and `POST /answers/{id}/vote`;
+// voting again replaces the earlier vote
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NewVote {
+    pub direction: VoteDirection,
+}
+
+// the target's score after the vote was cast or withdrawn
+#[derive(Debug, Serialize, Clone)]
+pub struct Score {
+    pub score: i64,
+}
+
+// a vote as part of the voter's account export; exactly one of the two IDs is set
+#[derive(Debug, Serialize, Clone)]
+pub struct CastVote {
+    pub question_id: Option<QuestionId>,
+    pub answer_id: Option<AnswerId>,
+    pub direction: VoteDirection,
+}
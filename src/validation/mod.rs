@@ -0,0 +1,175 @@
+// checks of account input that the types alone can't express: email syntax and the password policy
+use std::collections::HashSet;
+use std::sync::Arc;
+use lettre::Address;
+use handle_errors::{FieldError, WarpError}; // internal library
+
+use crate::config::PasswordConfig;
+use crate::types::account::NewAccount;
+
+const DEFAULT_BREACHED_LIST: &str = include_str!("breached_passwords.txt");
+// the longest address SMTP can deliver to; the `email` column holds 255 characters
+const MAX_EMAIL_LENGTH: usize = 254;
+
+/// Decides which new passwords are acceptable; existing passwords are never re-checked
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    // lowercase, so changing the case of a breached password doesn't get it through
+    breached: Arc<HashSet<String>>,
+}
+
+impl PasswordPolicy {
+    pub fn from_config(config: &PasswordConfig) -> Result<Self, std::io::Error> {
+        let list = match &config.breached_list {
+            Some(path) => std::fs::read_to_string(path)?,
+            None => DEFAULT_BREACHED_LIST.to_owned(),
+        };
+        // one password per line; empty lines and lines starting with `#` are skipped
+        let breached = list.lines()
+            .map(|line| line.trim().to_lowercase())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        Ok(Self {
+            min_length: config.min_length,
+            max_length: config.max_length,
+            breached: Arc::new(breached),
+        })
+    }
+
+    // every problem with `password`, reported under `field`; `email` is the address of the
+    // account, if it is known before the password is set
+    pub fn check(&self, field: &str, password: &str, email: Option<&str>) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let length = password.chars().count();
+        if length < self.min_length {
+            errors.push(FieldError::new(field, format!("must be at least {} characters long", self.min_length)));
+        } else if length > self.max_length {
+            errors.push(FieldError::new(field, format!("must be at most {} characters long", self.max_length)));
+        }
+        let password = password.to_lowercase();
+        if self.breached.contains(&password) {
+            errors.push(FieldError::new(field, "is too common, it appears in lists of breached passwords"));
+        } else if let Some(email) = email {
+            let email = email.trim().to_lowercase();
+            let local_part = email.split('@').next().unwrap_or_default();
+            if password == email || password == local_part {
+                errors.push(FieldError::new(field, "must not be the email address"));
+            }
+        }
+        errors
+    }
+
+    pub fn validate(&self, field: &str, password: &str, email: Option<&str>) -> Result<(), WarpError> {
+        let errors = self.check(field, password, email);
+        if !errors.is_empty() {
+            return Err(WarpError::ValidationError(errors));
+        }
+        Ok(())
+    }
+}
+
+// returns the address without surrounding whitespace; anything the mailer couldn't send to is rejected
+pub fn check_email(field: &str, email: &str) -> Result<String, FieldError> {
+    let email = email.trim();
+    if email.is_empty() {
+        return Err(FieldError::new(field, "must not be empty"));
+    }
+    if email.chars().count() > MAX_EMAIL_LENGTH {
+        return Err(FieldError::new(field, format!("must be at most {} characters long", MAX_EMAIL_LENGTH)));
+    }
+    match email.parse::<Address>() {
+        // `user@localhost` is a valid address, but not one a public service can reach
+        Ok(address) if address.domain().contains('.') => Ok(email.to_owned()),
+        _ => Err(FieldError::new(field, "must be a valid email address")),
+    }
+}
+
+/// Checks a registration, reporting every invalid field at once; the email comes back trimmed
+pub fn validate_new_account(account: NewAccount, policy: &PasswordPolicy) -> Result<NewAccount, WarpError> {
+    let mut errors = Vec::new();
+    let email = match check_email("email", &account.email) {
+        Ok(email) => email,
+        Err(e) => {
+            errors.push(e);
+            account.email
+        },
+    };
+    errors.extend(policy.check("password", &account.password, Some(&email)));
+    if !errors.is_empty() {
+        return Err(WarpError::ValidationError(errors));
+    }
+    Ok(NewAccount {
+        email,
+        password: account.password,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy::from_config(&PasswordConfig::default()).unwrap()
+    }
+
+    fn messages(errors: &[FieldError]) -> Vec<&str> {
+        errors.iter().map(|error| error.message.as_str()).collect()
+    }
+
+    #[test]
+    fn accepts_a_good_password() {
+        assert!(policy().check("password", "correct horse battery staple", Some("jane@example.com")).is_empty());
+    }
+
+    #[test]
+    fn enforces_the_length_in_characters() {
+        let policy = PasswordPolicy { min_length: 4, max_length: 6, breached: Arc::default() };
+        assert_eq!(messages(&policy.check("password", "abc", None)), vec!["must be at least 4 characters long"]);
+        assert_eq!(messages(&policy.check("password", "abcdefg", None)), vec!["must be at most 6 characters long"]);
+        // six characters, but twelve bytes
+        assert!(policy.check("password", "ääääää", None).is_empty());
+    }
+
+    #[test]
+    fn rejects_breached_passwords_in_any_case() {
+        let errors = policy().check("new_password", "PassWord1", None);
+        assert_eq!(errors[0].field, "new_password");
+        assert_eq!(messages(&errors), vec!["is too common, it appears in lists of breached passwords"]);
+    }
+
+    #[test]
+    fn rejects_the_email_address_as_password() {
+        let policy = policy();
+        for password in ["Jane.Doe@Example.com", "jane.doe"] {
+            assert_eq!(messages(&policy.check("password", password, Some(" jane.doe@example.com "))), vec!["must not be the email address"]);
+        }
+        assert!(matches!(policy.validate("password", "jane.doe", Some("jane.doe@example.com")), Err(WarpError::ValidationError(_))));
+    }
+
+    #[test]
+    fn trims_valid_emails() {
+        assert_eq!(check_email("email", "  jane@example.com ").unwrap(), "jane@example.com");
+    }
+
+    #[test]
+    fn rejects_invalid_emails() {
+        let too_long = format!("{}@example.com", "a".repeat(MAX_EMAIL_LENGTH));
+        for email in ["", "   ", "jane", "jane@", "@example.com", "jane@localhost", too_long.as_str()] {
+            assert!(check_email("email", email).is_err(), "{:?} was accepted", email);
+        }
+    }
+
+    #[test]
+    fn reports_email_and_password_problems_together() {
+        let account = NewAccount { email: "nobody".to_owned(), password: "short".to_owned() };
+        match validate_new_account(account, &policy()) {
+            Err(WarpError::ValidationError(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+                assert_eq!(fields, vec!["email", "password"]);
+            },
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+}